@@ -7,6 +7,7 @@ use crate::structs::CentrifugeError;
 use crate::structs::raw;
 use crate::structs::ether::{self, Ether};
 use crate::link::DataLink;
+use self::udp::PortHeuristic;
 
 pub mod arp;
 pub mod udp;
@@ -16,13 +17,13 @@ pub mod rtcp;
 
 
 #[inline]
-pub fn parse(link: &DataLink, data: &[u8]) -> raw::Raw {
+pub fn parse(link: &DataLink, data: &[u8], ports: PortHeuristic) -> raw::Raw {
     match *link {
-        DataLink::Ethernet => match parse_eth(data) {
+        DataLink::Ethernet => match parse_eth(data, ports) {
             Ok(eth) => eth,
             Err(_)  => Unknown(data.to_vec()),
         },
-        DataLink::Tun => parse_tun(data),
+        DataLink::Tun => parse_tun(data, ports),
         DataLink::RadioTap => {
             Unknown(data.to_vec())
         },
@@ -30,15 +31,15 @@ pub fn parse(link: &DataLink, data: &[u8]) -> raw::Raw {
 }
 
 #[inline]
-pub fn parse_eth(data: &[u8]) -> Result<raw::Raw, CentrifugeError> {
+pub fn parse_eth(data: &[u8], ports: PortHeuristic) -> Result<raw::Raw, CentrifugeError> {
     use crate::structs::ether::Ether::Unknown;
     if let Ok((remaining, eth_frame)) = ethernet::parse_ethernet_frame(data) {
         let inner = match eth_frame.ethertype {
-            EtherType::IPv4 => match parse_ipv4(remaining) {
+            EtherType::IPv4 => match parse_ipv4(remaining, ports) {
                 Ok(ipv4) => ipv4,
                 Err(_)   => Unknown(remaining.to_vec()),
             },
-            EtherType::IPv6 => match parse_ipv6(remaining) {
+            EtherType::IPv6 => match parse_ipv6(remaining, ports) {
                 Ok(ipv6) => ipv6,
                 Err(_)   => Unknown(remaining.to_vec()),
             },
@@ -57,9 +58,9 @@ pub fn parse_eth(data: &[u8]) -> Result<raw::Raw, CentrifugeError> {
 }
 
 #[inline]
-pub fn parse_tun(data: &[u8]) -> raw::Raw {
+pub fn parse_tun(data: &[u8], ports: PortHeuristic) -> raw::Raw {
     raw::Raw::Tun(
-        if let Ok(ipv4) = parse_ipv4(data) {
+        if let Ok(ipv4) = parse_ipv4(data, ports) {
             ipv4
         } else {
             Ether::Unknown(data.to_vec())
@@ -68,7 +69,7 @@ pub fn parse_tun(data: &[u8]) -> raw::Raw {
 }
 
 #[inline]
-pub fn parse_ipv4(data: &[u8]) -> Result<ether::Ether, CentrifugeError> {
+pub fn parse_ipv4(data: &[u8], ports: PortHeuristic) -> Result<ether::Ether, CentrifugeError> {
     use crate::structs::ipv4::IPv4::*;
 
     if let Ok((remaining, ip_hdr)) = ipv4::parse_ipv4_header(data) {
@@ -77,7 +78,7 @@ pub fn parse_ipv4(data: &[u8]) -> Result<ether::Ether, CentrifugeError> {
                 Ok((tcp_hdr, tcp)) => TCP(tcp_hdr, tcp),
                 Err(_) => Unknown(remaining.to_vec()),
             },
-            IPProtocol::UDP => match udp::parse(remaining) {
+            IPProtocol::UDP => match udp::parse(remaining, ports) {
                 Ok((udp_hdr, udp)) => UDP(udp_hdr, udp),
                 Err(_) => Unknown(remaining.to_vec()),
             },
@@ -92,7 +93,7 @@ pub fn parse_ipv4(data: &[u8]) -> Result<ether::Ether, CentrifugeError> {
 }
 
 #[inline]
-pub fn parse_ipv6(data: &[u8]) -> Result<ether::Ether, CentrifugeError> {
+pub fn parse_ipv6(data: &[u8], ports: PortHeuristic) -> Result<ether::Ether, CentrifugeError> {
     use crate::structs::ipv6::IPv6::*;
 
     if let Ok((remaining, ip_hdr)) = ipv6::parse_ipv6_header(data) {
@@ -101,7 +102,7 @@ pub fn parse_ipv6(data: &[u8]) -> Result<ether::Ether, CentrifugeError> {
                 Ok((tcp_hdr, tcp)) => TCP(tcp_hdr, tcp),
                 Err(_) => Unknown(remaining.to_vec()),
             },
-            IPProtocol::UDP => match udp::parse(remaining) {
+            IPProtocol::UDP => match udp::parse(remaining, ports) {
                 Ok((udp_hdr, udp)) => UDP(udp_hdr, udp),
                 Err(_) => Unknown(remaining.to_vec()),
             },
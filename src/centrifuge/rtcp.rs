@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-use crate::structs::{self, CentrifugeError, rtcp::RtcpHeader};
+use crate::structs::{self, CentrifugeError, rtcp::*};
 pub fn extract(remaining: &[u8]) -> Result<structs::rtcp::RTCP, CentrifugeError> {
     if remaining.len() < 12 {
         return Err(structs::CentrifugeError::WrongProtocol);
@@ -11,23 +11,606 @@ pub fn extract(remaining: &[u8]) -> Result<structs::rtcp::RTCP, CentrifugeError>
     }
     let rtp_payload = remaining[1] & 0x7f;
     if rtp_payload >= 64 && rtp_payload < 96 {
-        let payload = remaining[1];
-        let header = RtcpHeader {
-            rc: remaining[0] & 0x1f,
-            padding: (remaining[0] >> 5) & 1,
-            version: rtp_version,
-            payload,
-            length: u16::from_be_bytes(remaining[2..4].try_into().unwrap()),
-            ssrc: u32::from_be_bytes(remaining[4..8].try_into().unwrap()),
-        };
-        if remaining.len() < 8 {
-            return Err(structs::CentrifugeError::WrongProtocol);    
+        let header = parse_header(remaining);
+        let mut packets = Vec::new();
+        let mut data = remaining;
+        // a malformed sub-packet or the SRTCP index and auth tag end the
+        // compound packet, the sub-packets before it are kept
+        while !data.is_empty() {
+            // the SRTCP index has the E bit set and looks like version 2
+            if data.len() < 4 || data[0] >> 6 != 2 || !(192..=223).contains(&data[1]) {
+                break;
+            }
+            let len = (be16(&data[2..]) as usize + 1) * 4;
+            if data.len() < len {
+                break;
+            }
+            let (pkt, rest) = data.split_at(len);
+            match parse_packet(pkt) {
+                Ok(packet) => packets.push(packet),
+                Err(_) => break,
+            }
+            data = rest;
+        }
+        // without a single valid sub-packet this isn't rtcp at all
+        if packets.is_empty() {
+            return Err(structs::CentrifugeError::ParsingError);
         }
         return Ok(structs::rtcp::RTCP {
             header,
-            payload: remaining[8..].to_owned(),
+            packets,
+            len: remaining.len(),
         });
     } else {
         return Err(structs::CentrifugeError::WrongProtocol);
     }
 }
+
+fn parse_header(pkt: &[u8]) -> RtcpHeader {
+    RtcpHeader {
+        rc: pkt[0] & 0x1f,
+        padding: (pkt[0] >> 5) & 1,
+        version: pkt[0] >> 6,
+        payload: pkt[1],
+        length: be16(&pkt[2..]),
+        ssrc: if pkt.len() >= 8 { be32(&pkt[4..]) } else { 0 },
+    }
+}
+
+/// parse one sub-packet of a compound packet, including its common header
+fn parse_packet(pkt: &[u8]) -> Result<RtcpPacket, CentrifugeError> {
+    let header = parse_header(pkt);
+    let mut body = &pkt[4..];
+    if header.padding != 0 {
+        let pad = *body.last().ok_or(CentrifugeError::ParsingError)? as usize;
+        if pad == 0 || pad > body.len() {
+            return Err(CentrifugeError::ParsingError);
+        }
+        body = &body[..(body.len() - pad)];
+    }
+    let count = header.rc as usize;
+    let packet = match header.payload {
+        RTCP_SR => {
+            if body.len() < 24 + count * 24 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            RtcpPacket::SenderReport(SenderReport {
+                ssrc: be32(body),
+                sender_info: SenderInfo {
+                    ntp_sec: be32(&body[4..]),
+                    ntp_frac: be32(&body[8..]),
+                    rtp_timestamp: be32(&body[12..]),
+                    packet_count: be32(&body[16..]),
+                    octet_count: be32(&body[20..]),
+                },
+                reports: parse_report_blocks(&body[24..], count),
+            })
+        }
+        RTCP_RR => {
+            if body.len() < 4 + count * 24 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            RtcpPacket::ReceiverReport(ReceiverReport {
+                ssrc: be32(body),
+                reports: parse_report_blocks(&body[4..], count),
+            })
+        }
+        RTCP_SDES => RtcpPacket::SourceDescription(parse_sdes(body, count)?),
+        RTCP_BYE => {
+            if body.len() < count * 4 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            let sources = (0..count).map(|i| be32(&body[(i * 4)..])).collect();
+            let rest = &body[(count * 4)..];
+            let reason = match rest.first() {
+                Some(&len) if rest.len() > len as usize => {
+                    Some(String::from_utf8_lossy(&rest[1..(1 + len as usize)]).into_owned())
+                }
+                _ => None,
+            };
+            RtcpPacket::Goodbye(Goodbye { sources, reason })
+        }
+        RTCP_APP => {
+            if body.len() < 8 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            RtcpPacket::App(App {
+                ssrc: be32(body),
+                subtype: header.rc,
+                name: String::from_utf8_lossy(&body[4..8]).into_owned(),
+                data: body[8..].to_vec(),
+            })
+        }
+        RTCP_XR => {
+            if body.len() < 4 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            RtcpPacket::ExtendedReport(ExtendedReport {
+                ssrc: be32(body),
+                blocks: parse_xr_blocks(&body[4..])?,
+            })
+        }
+        RTCP_RTPFB | RTCP_PSFB => {
+            if body.len() < 8 {
+                return Err(CentrifugeError::ParsingError);
+            }
+            let fmt = header.rc;
+            let sender_ssrc = be32(body);
+            let media_ssrc = be32(&body[4..]);
+            let fci = &body[8..];
+            if header.payload == RTCP_RTPFB {
+                RtcpPacket::TransportFeedback(match fmt {
+                    1 => TransportFeedback::Nack(Nack {
+                        sender_ssrc,
+                        media_ssrc,
+                        lost: parse_nack(fci),
+                    }),
+                    15 => TransportFeedback::TransportCc(parse_twcc(sender_ssrc, media_ssrc, fci)?),
+                    _ => TransportFeedback::Unknown { fmt, sender_ssrc, media_ssrc, fci: fci.to_vec() },
+                })
+            } else {
+                RtcpPacket::PayloadFeedback(match fmt {
+                    1 => PayloadFeedback::Pli { sender_ssrc, media_ssrc },
+                    4 => PayloadFeedback::Fir {
+                        sender_ssrc,
+                        entries: fci
+                            .chunks_exact(8)
+                            .map(|e| FirEntry { ssrc: be32(e), seq: e[4] })
+                            .collect(),
+                    },
+                    15 if fci.len() >= 8 && &fci[..4] == b"REMB" => {
+                        let num = fci[4] as usize;
+                        let exp = fci[5] >> 2;
+                        let mantissa = (be32(&fci[4..]) & 0x3ffff) as u64;
+                        PayloadFeedback::Remb(Remb {
+                            sender_ssrc,
+                            bitrate: mantissa << exp.min(40),
+                            ssrcs: fci[8..].chunks_exact(4).take(num).map(be32).collect(),
+                        })
+                    }
+                    _ => PayloadFeedback::Unknown { fmt, sender_ssrc, media_ssrc, fci: fci.to_vec() },
+                })
+            }
+        }
+        _ => RtcpPacket::Unknown(header, body.to_vec()),
+    };
+    Ok(packet)
+}
+
+/// Generic NACK, each (PID, BLP) entry names up to 17 lost packets
+fn parse_nack(fci: &[u8]) -> Vec<u16> {
+    let mut lost = Vec::new();
+    for e in fci.chunks_exact(4) {
+        let pid = be16(e);
+        let blp = be16(&e[2..]);
+        lost.push(pid);
+        for i in 0..16 {
+            if blp & (1 << i) != 0 {
+                lost.push(pid.wrapping_add(i + 1));
+            }
+        }
+    }
+    lost
+}
+
+/// transport-wide congestion control feedback
+fn parse_twcc(sender_ssrc: u32, media_ssrc: u32, fci: &[u8]) -> Result<TransportCc, CentrifugeError> {
+    if fci.len() < 8 {
+        return Err(CentrifugeError::ParsingError);
+    }
+    let base_seq = be16(fci);
+    let status_count = be16(&fci[2..]);
+    // sign extend 24 bit
+    let reference_time = (be32(&fci[4..]) as i32) >> 8;
+    let fb_count = fci[7];
+
+    // packet status symbols: 0 not received, 1 small delta, 2 large delta
+    let mut symbols = Vec::with_capacity(status_count as usize);
+    let mut pos = 8;
+    while symbols.len() < status_count as usize {
+        let chunk = be16(fci.get(pos..(pos + 2)).ok_or(CentrifugeError::ParsingError)?);
+        pos += 2;
+        let remaining = status_count as usize - symbols.len();
+        if chunk & 0x8000 == 0 {
+            // run length chunk
+            let symbol = ((chunk >> 13) & 0x3) as u8;
+            let run = (chunk & 0x1fff) as usize;
+            symbols.extend(std::iter::repeat_n(symbol, run.min(remaining)));
+        } else if chunk & 0x4000 == 0 {
+            // status vector chunk, 14 one-bit symbols
+            symbols.extend((0..14).rev().map(|i| ((chunk >> i) & 0x1) as u8).take(remaining));
+        } else {
+            // status vector chunk, 7 two-bit symbols
+            symbols.extend((0..7).rev().map(|i| ((chunk >> (i * 2)) & 0x3) as u8).take(remaining));
+        }
+    }
+
+    let mut packets = Vec::with_capacity(symbols.len());
+    for (i, symbol) in symbols.into_iter().enumerate() {
+        let delta = match symbol {
+            1 => {
+                let d = *fci.get(pos).ok_or(CentrifugeError::ParsingError)?;
+                pos += 1;
+                Some(d as i32)
+            }
+            2 | 3 => {
+                let d = be16(fci.get(pos..(pos + 2)).ok_or(CentrifugeError::ParsingError)?);
+                pos += 2;
+                Some(d as i16 as i32)
+            }
+            _ => None,
+        };
+        packets.push(TwccPacket {
+            seq: base_seq.wrapping_add(i as u16),
+            delta,
+        });
+    }
+    Ok(TransportCc {
+        sender_ssrc,
+        media_ssrc,
+        base_seq,
+        status_count,
+        reference_time,
+        fb_count,
+        packets,
+    })
+}
+
+/// RFC 3611 report blocks
+fn parse_xr_blocks(mut data: &[u8]) -> Result<Vec<XrBlock>, CentrifugeError> {
+    let mut blocks = Vec::new();
+    while data.len() >= 4 {
+        let bt = data[0];
+        let type_specific = data[1];
+        let len = be16(&data[2..]) as usize * 4;
+        let b = data.get(4..(4 + len)).ok_or(CentrifugeError::ParsingError)?;
+        let block = match bt {
+            1 | 2 if b.len() >= 8 => {
+                let rle = RleReport {
+                    thinning: type_specific & 0x0f,
+                    ssrc: be32(b),
+                    begin_seq: be16(&b[4..]),
+                    end_seq: be16(&b[6..]),
+                    chunks: b[8..].chunks_exact(2).map(be16).filter(|c| *c != 0).collect(),
+                };
+                if bt == 1 {
+                    XrBlock::LossRle(rle)
+                } else {
+                    XrBlock::DuplicateRle(rle)
+                }
+            }
+            4 if b.len() >= 8 => XrBlock::ReceiverReferenceTime {
+                ntp_sec: be32(b),
+                ntp_frac: be32(&b[4..]),
+            },
+            5 => XrBlock::Dlrr(
+                b.chunks_exact(12)
+                    .map(|i| DlrrItem {
+                        ssrc: be32(i),
+                        lrr: be32(&i[4..]),
+                        dlrr: be32(&i[8..]),
+                    })
+                    .collect(),
+            ),
+            6 if b.len() >= 36 => XrBlock::StatisticsSummary(StatisticsSummary {
+                flags: type_specific >> 4,
+                ssrc: be32(b),
+                begin_seq: be16(&b[4..]),
+                end_seq: be16(&b[6..]),
+                lost_packets: be32(&b[8..]),
+                dup_packets: be32(&b[12..]),
+                min_jitter: be32(&b[16..]),
+                max_jitter: be32(&b[20..]),
+                mean_jitter: be32(&b[24..]),
+                dev_jitter: be32(&b[28..]),
+                min_ttl: b[32],
+                max_ttl: b[33],
+                mean_ttl: b[34],
+                dev_ttl: b[35],
+            }),
+            7 if b.len() >= 32 => XrBlock::VoipMetrics(VoipMetrics {
+                ssrc: be32(b),
+                loss_rate: b[4],
+                discard_rate: b[5],
+                burst_density: b[6],
+                gap_density: b[7],
+                burst_duration: be16(&b[8..]),
+                gap_duration: be16(&b[10..]),
+                round_trip_delay: be16(&b[12..]),
+                end_system_delay: be16(&b[14..]),
+                signal_level: b[16] as i8,
+                noise_level: b[17] as i8,
+                rerl: b[18],
+                gmin: b[19],
+                r_factor: b[20],
+                ext_r_factor: b[21],
+                mos_lq: b[22],
+                mos_cq: b[23],
+                rx_config: b[24],
+                jb_nominal: be16(&b[26..]),
+                jb_maximum: be16(&b[28..]),
+                jb_abs_max: be16(&b[30..]),
+            }),
+            _ => XrBlock::Unknown(bt, b.to_vec()),
+        };
+        blocks.push(block);
+        data = &data[(4 + len)..];
+    }
+    Ok(blocks)
+}
+
+fn parse_report_blocks(data: &[u8], count: usize) -> Vec<ReportBlock> {
+    data.chunks_exact(24)
+        .take(count)
+        .map(|b| ReportBlock {
+            ssrc: be32(b),
+            fraction_lost: b[4],
+            // sign extend 24 bit
+            cumulative_lost: (be32(&b[4..]) << 8) as i32 >> 8,
+            highest_seq: be32(&b[8..]),
+            jitter: be32(&b[12..]),
+            lsr: be32(&b[16..]),
+            dlsr: be32(&b[20..]),
+        })
+        .collect()
+}
+
+fn parse_sdes(mut data: &[u8], count: usize) -> Result<Vec<SdesChunk>, CentrifugeError> {
+    let mut chunks = Vec::with_capacity(count);
+    for _ in 0..count {
+        if data.len() < 4 {
+            return Err(CentrifugeError::ParsingError);
+        }
+        let ssrc = be32(data);
+        let mut items = Vec::new();
+        let mut pos = 4;
+        loop {
+            match data.get(pos) {
+                // end of item list, the chunk is padded to a 32 bit boundary
+                Some(0) | None => {
+                    pos = (pos / 4 + 1) * 4;
+                    break;
+                }
+                Some(&kind) => {
+                    let len = *data.get(pos + 1).ok_or(CentrifugeError::ParsingError)? as usize;
+                    let value = data
+                        .get((pos + 2)..(pos + 2 + len))
+                        .ok_or(CentrifugeError::ParsingError)?;
+                    let text = || String::from_utf8_lossy(value).into_owned();
+                    items.push(match kind {
+                        1 => SdesItem::Cname(text()),
+                        2 => SdesItem::Name(text()),
+                        3 => SdesItem::Email(text()),
+                        4 => SdesItem::Phone(text()),
+                        5 => SdesItem::Loc(text()),
+                        6 => SdesItem::Tool(text()),
+                        7 => SdesItem::Note(text()),
+                        8 => SdesItem::Priv(value.to_vec()),
+                        x => SdesItem::Unknown(x, value.to_vec()),
+                    });
+                    pos += 2 + len;
+                }
+            }
+        }
+        chunks.push(SdesChunk { ssrc, items });
+        data = data.get(pos..).unwrap_or(&[]);
+    }
+    Ok(chunks)
+}
+
+#[inline]
+fn be16(data: &[u8]) -> u16 {
+    u16::from_be_bytes(data[..2].try_into().unwrap())
+}
+
+#[inline]
+fn be32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// twcc fci: base seq, status count, reference time, fb count, then chunks and deltas
+    fn twcc_fci(base_seq: u16, status_count: u16, reference_time: u32, chunks: &[u16], deltas: &[u8]) -> Vec<u8> {
+        let mut fci = Vec::new();
+        fci.extend_from_slice(&base_seq.to_be_bytes());
+        fci.extend_from_slice(&status_count.to_be_bytes());
+        fci.extend_from_slice(&(reference_time << 8 | 7).to_be_bytes());
+        for c in chunks.iter() {
+            fci.extend_from_slice(&c.to_be_bytes());
+        }
+        fci.extend_from_slice(deltas);
+        fci
+    }
+
+    fn deltas(twcc: &TransportCc) -> Vec<Option<i32>> {
+        twcc.packets.iter().map(|p| p.delta).collect()
+    }
+
+    #[test]
+    fn twcc_run_length_chunk() {
+        // draft-holmer-rmcat-transport-wide-cc-extensions-01 3.1.3:
+        // a run of 221 packets received with small deltas
+        let fci = twcc_fci(65530, 221, 0x10, &[0x20dd], &[4; 221]);
+        let twcc = parse_twcc(1, 2, &fci).unwrap();
+        assert_eq!(twcc.reference_time, 0x10);
+        assert_eq!(twcc.fb_count, 7);
+        assert_eq!(twcc.packets.len(), 221);
+        assert!(twcc.packets.iter().all(|p| p.delta == Some(4)));
+        // seqs wrap after the base
+        assert_eq!(twcc.packets[6].seq, 0);
+    }
+
+    #[test]
+    fn twcc_two_bit_status_vector() {
+        // symbols small, large, not received, small, not received, not received, large
+        let fci = twcc_fci(100, 7, 0, &[0xd842], &[0x08, 0xff, 0x38, 0x10, 0x01, 0x00]);
+        let twcc = parse_twcc(1, 2, &fci).unwrap();
+        assert_eq!(
+            deltas(&twcc),
+            vec![Some(8), Some(-200), None, Some(16), None, None, Some(256)]
+        );
+    }
+
+    #[test]
+    fn twcc_one_bit_status_vector_ends_mid_chunk() {
+        // 10 not received, then a 14 symbol vector of which only 10 count,
+        // the trailing set bits belong to no packet
+        let fci = twcc_fci(0, 20, 0, &[0x000a, 0xb41f], &[1, 2, 3, 4]);
+        let twcc = parse_twcc(1, 2, &fci).unwrap();
+        assert_eq!(twcc.packets.len(), 20);
+        let received: Vec<(u16, i32)> = twcc
+            .packets
+            .iter()
+            .filter_map(|p| Some((p.seq, p.delta?)))
+            .collect();
+        assert_eq!(received, vec![(10, 1), (11, 2), (13, 3), (19, 4)]);
+    }
+
+    #[test]
+    fn twcc_negative_reference_time() {
+        let fci = twcc_fci(0, 1, 0xffffff, &[0x2001], &[0]);
+        assert_eq!(parse_twcc(1, 2, &fci).unwrap().reference_time, -1);
+    }
+
+    #[test]
+    fn twcc_missing_deltas() {
+        let fci = twcc_fci(0, 2, 0, &[0x2002], &[1]);
+        assert!(parse_twcc(1, 2, &fci).is_err());
+    }
+
+    #[test]
+    fn remb() {
+        // draft-alvestrand-rmcat-remb: exp 3, mantissa 125000, two ssrcs
+        let pkt = [
+            0x8f, RTCP_PSFB, 0x00, 0x06,
+            0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x00,
+            b'R', b'E', b'M', b'B',
+            0x02, 0x0d, 0xe8, 0x48,
+            0x11, 0x11, 0x11, 0x11,
+            0x22, 0x22, 0x22, 0x22,
+        ];
+        let remb = match parse_packet(&pkt).unwrap() {
+            RtcpPacket::PayloadFeedback(PayloadFeedback::Remb(remb)) => remb,
+            other => panic!("not a REMB: {:?}", other),
+        };
+        assert_eq!(remb.sender_ssrc, 1);
+        assert_eq!(remb.bitrate, 1_000_000);
+        assert_eq!(remb.ssrcs, vec![0x11111111, 0x22222222]);
+    }
+
+    #[test]
+    fn compound_keeps_packets_before_srtcp_trailer() {
+        let mut data = vec![0x80, RTCP_RR, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05];
+        // SRTCP E flag and index, then an auth tag
+        data.extend_from_slice(&[0x80, 0x00, 0x00, 0x01, 0xde, 0xad, 0xbe, 0xef, 0x01, 0x02]);
+        let rtcp = extract(&data).unwrap();
+        assert_eq!(rtcp.packets.len(), 1);
+        assert!(matches!(rtcp.packets[0], RtcpPacket::ReceiverReport(ReceiverReport { ssrc: 5, .. })));
+    }
+
+    #[test]
+    fn malformed_first_packet_is_rejected() {
+        // RR announcing a report block it doesn't carry
+        let data = [0x81, RTCP_RR, 0x00, 0x02, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00];
+        assert!(extract(&data).is_err());
+    }
+
+    #[test]
+    fn non_rtcp_is_rejected() {
+        // legacy rtp with dynamic PT 72 and the marker bit set reads as an SR
+        // whose length is the rtp seq
+        let data = [0x80, 0xc8, 0x12, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0xaa, 0xbb];
+        assert!(extract(&data).is_err());
+        // version 2 header, but the length overruns the datagram
+        let data = [0x80, RTCP_RR, 0x00, 0x08, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00];
+        assert!(extract(&data).is_err());
+    }
+
+    /// XR packet from `ssrc` carrying the given report blocks
+    fn xr_pkt(blocks: &[u8]) -> Vec<u8> {
+        let words = (blocks.len() / 4 + 1) as u16;
+        let mut pkt = vec![0x80, RTCP_XR];
+        pkt.extend_from_slice(&words.to_be_bytes());
+        pkt.extend_from_slice(&[0x00, 0x00, 0x00, 0x09]);
+        pkt.extend_from_slice(blocks);
+        pkt
+    }
+
+    fn xr_blocks(pkt: &[u8]) -> Vec<XrBlock> {
+        match parse_packet(pkt).unwrap() {
+            RtcpPacket::ExtendedReport(xr) => {
+                assert_eq!(xr.ssrc, 9);
+                xr.blocks
+            }
+            other => panic!("not an XR: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn xr_receiver_reference_time_and_dlrr() {
+        let blocks = [
+            // RRT, 2 words of ntp time
+            0x04, 0x00, 0x00, 0x02,
+            0xe5, 0x00, 0x00, 0x01, 0x80, 0x00, 0x00, 0x00,
+            // DLRR with two sub-blocks
+            0x05, 0x00, 0x00, 0x06,
+            0x11, 0x11, 0x11, 0x11, 0x00, 0x01, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00,
+            0x22, 0x22, 0x22, 0x22, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            xr_blocks(&xr_pkt(&blocks)),
+            vec![
+                XrBlock::ReceiverReferenceTime { ntp_sec: 0xe5000001, ntp_frac: 0x80000000 },
+                XrBlock::Dlrr(vec![
+                    DlrrItem { ssrc: 0x11111111, lrr: 0x00018000, dlrr: 0x8000 },
+                    DlrrItem { ssrc: 0x22222222, lrr: 0, dlrr: 0 },
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn xr_voip_metrics() {
+        let blocks = [
+            0x07, 0x00, 0x00, 0x08,
+            0x00, 0x00, 0x00, 0x05,
+            // loss 10/256, discard 2/256, burst and gap density
+            0x0a, 0x02, 0x40, 0x03,
+            // burst 120 ms, gap 5000 ms
+            0x00, 0x78, 0x13, 0x88,
+            // round trip 80 ms, end system 40 ms
+            0x00, 0x50, 0x00, 0x28,
+            // signal -20 dBm, noise -70 dBm, rerl, gmin 16
+            0xec, 0xba, 0x7f, 0x10,
+            // R 85, ext R unavailable, MOS-LQ 4.1, MOS-CQ 3.9
+            0x55, 0x7f, 0x29, 0x27,
+            // adaptive jb, reserved, nominal 60, max 120, abs max 400
+            0x83, 0x00, 0x00, 0x3c, 0x00, 0x78, 0x01, 0x90,
+        ];
+        let m = match &xr_blocks(&xr_pkt(&blocks))[..] {
+            [XrBlock::VoipMetrics(m)] => m.clone(),
+            other => panic!("not voip metrics: {:?}", other),
+        };
+        assert_eq!(m.ssrc, 5);
+        assert_eq!((m.loss_rate, m.discard_rate, m.burst_density, m.gap_density), (10, 2, 64, 3));
+        assert_eq!((m.burst_duration, m.gap_duration), (120, 5000));
+        assert_eq!((m.round_trip_delay, m.end_system_delay), (80, 40));
+        assert_eq!((m.signal_level, m.noise_level, m.gmin), (-20, -70, 16));
+        assert_eq!((m.r_factor, m.ext_r_factor, m.mos_lq, m.mos_cq), (85, 127, 41, 39));
+        assert_eq!((m.rx_config, m.jb_nominal, m.jb_maximum, m.jb_abs_max), (0x83, 60, 120, 400));
+    }
+
+    #[test]
+    fn xr_truncated_block() {
+        // the block announces 8 words, only 2 follow
+        let blocks = [0x07, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x05, 0x0a, 0x02, 0x40, 0x03];
+        assert!(parse_packet(&xr_pkt(&blocks)).is_err());
+        // a known type too short for its fields is kept undecoded
+        let blocks = [0x04, 0x00, 0x00, 0x01, 0xe5, 0x00, 0x00, 0x01];
+        assert_eq!(xr_blocks(&xr_pkt(&blocks)), vec![XrBlock::Unknown(4, vec![0xe5, 0x00, 0x00, 0x01])]);
+    }
+}
@@ -1,6 +1,7 @@
 use std::convert::TryInto;
 
-use crate::structs::{self, CentrifugeError, rtp::RtpHeader};
+use crate::structs::{self, CentrifugeError, rtp::{HeaderExtension, RtpHeader}};
+
 pub fn extract(remaining: &[u8]) -> Result<structs::rtp::RTP, CentrifugeError> {
     if remaining.len() < 12 {
         return Err(structs::CentrifugeError::WrongProtocol);
@@ -9,41 +10,212 @@ pub fn extract(remaining: &[u8]) -> Result<structs::rtp::RTP, CentrifugeError> {
     if rtp_version != 2 {
         return Err(structs::CentrifugeError::WrongProtocol);
     }
+    // telling rtp from rtcp by payload type is up to the caller, legacy
+    // dynamic payload types 72-76 overlap the rtcp range
     let rtp_payload = remaining[1] & 0x7f;
-    if rtp_payload < 64 || rtp_payload >= 96 {
-        let header = RtpHeader {
-            cc: remaining[0] & 0x0f,
-            extension: (remaining[0] >> 4) & 1,
-            padding: (remaining[0] >> 5) & 1,
-            version: rtp_version,
-            payload: rtp_payload,
-            marker: (remaining[1] >> 7) & 1,
-            seqnum: u16::from_be_bytes(remaining[2..4].try_into().unwrap()),
-            timestamp: u32::from_be_bytes(remaining[4..8].try_into().unwrap()),
-            ssrc: u32::from_be_bytes(remaining[8..12].try_into().unwrap()),
-        };
-        if remaining.len() < 12 + header.cc as usize * 4 {
+    let mut header = RtpHeader {
+        cc: remaining[0] & 0x0f,
+        extension: (remaining[0] >> 4) & 1,
+        padding: (remaining[0] >> 5) & 1,
+        version: rtp_version,
+        payload: rtp_payload,
+        marker: (remaining[1] >> 7) & 1,
+        seqnum: u16::from_be_bytes(remaining[2..4].try_into().unwrap()),
+        timestamp: u32::from_be_bytes(remaining[4..8].try_into().unwrap()),
+        ssrc: u32::from_be_bytes(remaining[8..12].try_into().unwrap()),
+        csrcs: vec![],
+        extensions: vec![],
+    };
+    if remaining.len() < 12 + header.cc as usize * 4 {
+        return Err(structs::CentrifugeError::WrongProtocol);    
+    }
+    header.csrcs = remaining[12..12 + header.cc as usize * 4]
+        .chunks(4)
+        .map(|c| u32::from_be_bytes(c.try_into().unwrap()))
+        .collect();
+    let payload_offset;
+    if header.extension != 0 {
+        let ext_offset = 12 + header.cc as usize * 4;
+        if remaining.len() < ext_offset + 4 {
             return Err(structs::CentrifugeError::WrongProtocol);    
         }
-        let payload_offset;
-        if header.extension != 0 {
-            if remaining.len() < 12 + header.cc as usize * 4 + 4 {
-                return Err(structs::CentrifugeError::WrongProtocol);    
+        let profile = u16::from_be_bytes(remaining[ext_offset..ext_offset + 2].try_into().unwrap());
+        let ext_len = u16::from_be_bytes(remaining[ext_offset + 2..ext_offset + 4].try_into().unwrap());
+        payload_offset = ext_offset + 4 + ext_len as usize * 4;
+        if remaining.len() < payload_offset {
+            return Err(structs::CentrifugeError::WrongProtocol);
+        }
+        header.extensions = parse_extensions(profile, &remaining[ext_offset + 4..payload_offset]);
+    } else {
+        payload_offset = 12 + header.cc as usize * 4;
+    }
+    if remaining.len() < 12 + payload_offset {
+        return Err(structs::CentrifugeError::WrongProtocol);    
+    }
+    let mut payload_end = remaining.len();
+    let mut padding_len = 0;
+    if header.padding != 0 {
+        // the last octet counts the padding, itself included
+        padding_len = remaining[payload_end - 1] as usize;
+        if padding_len == 0 || payload_offset + padding_len > payload_end {
+            return Err(structs::CentrifugeError::WrongProtocol);
+        }
+        payload_end -= padding_len;
+    }
+    Ok(structs::rtp::RTP {
+        header,
+        payload: remaining[payload_offset..payload_end].to_owned(),
+        len: remaining.len(),
+        padding_len,
+    })
+}
+
+/// RFC 8285 one-byte (0xBEDE) and two-byte (0x100X) extension elements,
+/// other profiles are left undecoded
+fn parse_extensions(profile: u16, mut data: &[u8]) -> Vec<HeaderExtension> {
+    let one_byte = profile == 0xBEDE;
+    if !one_byte && profile & 0xfff0 != 0x1000 {
+        return vec![];
+    }
+    let mut extensions = vec![];
+    while !data.is_empty() {
+        // padding between elements
+        if data[0] == 0 {
+            data = &data[1..];
+            continue;
+        }
+        let (id, len, offset) = if one_byte {
+            // id 15 is reserved and stops processing
+            if data[0] >> 4 == 15 {
+                break;
             }
-            let ext_len = &remaining[(12 + header.cc as usize * 4 + 2)..];
-            let ext_len = u16::from_be_bytes(ext_len[..2].try_into().unwrap());
-            payload_offset = 12 + header.cc as usize * 4 + 4 + ext_len as usize * 4;
+            (data[0] >> 4, (data[0] & 0x0f) as usize + 1, 1)
         } else {
-            payload_offset = 12 + header.cc as usize * 4;
-        }
-        if remaining.len() < 12 + payload_offset {
-            return Err(structs::CentrifugeError::WrongProtocol);    
+            if data.len() < 2 {
+                break;
+            }
+            (data[0], data[1] as usize, 2)
+        };
+        if data.len() < offset + len {
+            break;
         }
-        return Ok(structs::rtp::RTP {
-            header, 
-            payload: remaining[payload_offset..].to_owned(),
+        extensions.push(HeaderExtension {
+            id,
+            data: data[offset..offset + len].to_vec(),
         });
-    } else {
-        return Err(structs::CentrifugeError::WrongProtocol);
+        data = &data[offset + len..];
+    }
+    extensions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ext(id: u8, data: &[u8]) -> HeaderExtension {
+        HeaderExtension { id, data: data.to_vec() }
+    }
+
+    #[test]
+    fn one_byte_extensions() {
+        // RFC 8285 4.2: id 1 with 1 byte, padding, id 2 with 3 bytes, padding to 32 bits
+        let data = [0x10, 0xaa, 0x00, 0x22, 0x01, 0x02, 0x03, 0x00];
+        assert_eq!(parse_extensions(0xBEDE, &data), vec![ext(1, &[0xaa]), ext(2, &[0x01, 0x02, 0x03])]);
+    }
+
+    #[test]
+    fn two_byte_extensions() {
+        // RFC 8285 4.3: a zero length element, padding, then id 20 with 2 bytes
+        let data = [0x01, 0x00, 0x00, 0x14, 0x02, 0xbe, 0xef, 0x00];
+        assert_eq!(parse_extensions(0x1000, &data), vec![ext(1, &[]), ext(20, &[0xbe, 0xef])]);
+        // the low 4 bits of the profile are app bits
+        assert_eq!(parse_extensions(0x100f, &data).len(), 2);
+    }
+
+    #[test]
+    fn one_byte_id_15_stops_processing() {
+        let data = [0x10, 0xaa, 0xf0, 0x10, 0xbb, 0x00, 0x00, 0x00];
+        assert_eq!(parse_extensions(0xBEDE, &data), vec![ext(1, &[0xaa])]);
+    }
+
+    #[test]
+    fn truncated_element() {
+        // id 3 announces 4 bytes, only 2 follow
+        assert_eq!(parse_extensions(0xBEDE, &[0x10, 0xaa, 0x33, 0x01, 0x02]), vec![ext(1, &[0xaa])]);
+        assert_eq!(parse_extensions(0x1000, &[0x05, 0x04, 0x01, 0x02]), vec![]);
+        assert_eq!(parse_extensions(0x1000, &[0x00, 0x05]), vec![]);
+    }
+
+    #[test]
+    fn unknown_profile() {
+        assert_eq!(parse_extensions(0xABAC, &[0x10, 0xaa, 0x00, 0x00]), vec![]);
+    }
+
+    #[test]
+    fn extensions_in_packet() {
+        let mut pkt = vec![0x90, 0x6f, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03];
+        pkt.extend_from_slice(&[0xbe, 0xde, 0x00, 0x01, 0x10, 0xaa, 0x00, 0x00]);
+        pkt.extend_from_slice(&[0x55; 12]);
+        let rtp = extract(&pkt).unwrap();
+        assert_eq!(rtp.header.extension(1), Some(&[0xaa][..]));
+        assert_eq!(rtp.payload, vec![0x55; 12]);
+    }
+
+    #[test]
+    fn csrc_list() {
+        // CC=2, the payload starts after the csrcs
+        let mut pkt = vec![0x82, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03];
+        pkt.extend_from_slice(&[0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+        pkt.extend_from_slice(&[0x55; 12]);
+        let rtp = extract(&pkt).unwrap();
+        assert_eq!(rtp.header.cc, 2);
+        assert_eq!(rtp.header.csrcs, vec![0x11111111, 0x22222222]);
+        assert_eq!(rtp.payload, vec![0x55; 12]);
+    }
+
+    #[test]
+    fn csrc_list_truncated() {
+        // CC=15 announces 60 bytes of csrcs
+        let mut pkt = vec![0x8f, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03];
+        pkt.extend_from_slice(&[0x11; 16]);
+        assert!(extract(&pkt).is_err());
+    }
+
+    fn padded(body: &[u8]) -> Vec<u8> {
+        let mut pkt = vec![0xa0, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03];
+        pkt.extend_from_slice(body);
+        pkt
+    }
+
+    #[test]
+    fn padding_is_stripped() {
+        let mut body = vec![0x55; 12];
+        body.extend_from_slice(&[0x00, 0x00, 0x00, 0x04]);
+        let rtp = extract(&padded(&body)).unwrap();
+        assert_eq!(rtp.payload, vec![0x55; 12]);
+        assert_eq!(rtp.padding_len, 4);
+        assert_eq!(rtp.len, 28);
+        assert!(!rtp.padding_only());
+    }
+
+    #[test]
+    fn padding_only() {
+        let mut body = vec![0x00; 15];
+        body.push(16);
+        let rtp = extract(&padded(&body)).unwrap();
+        assert!(rtp.payload.is_empty());
+        assert_eq!(rtp.padding_len, 16);
+        assert!(rtp.padding_only());
+    }
+
+    #[test]
+    fn invalid_padding_length() {
+        // a zero count
+        let mut body = vec![0x55; 15];
+        body.push(0);
+        assert!(extract(&padded(&body)).is_err());
+        // a count reaching into the header
+        body[15] = 17;
+        assert!(extract(&padded(&body)).is_err());
     }
 }
@@ -1,4 +1,6 @@
-use std::str::from_utf8;
+use std::str::{from_utf8, FromStr};
+
+use crate::errors::*;
 
 use pktparse::udp::{self, UdpHeader};
 use crate::centrifuge::rtp;
@@ -7,9 +9,39 @@ use crate::structs::CentrifugeError;
 use crate::structs::udp::UDP;
 
 
-pub fn parse(remaining: &[u8]) -> Result<(udp::UdpHeader, UDP), CentrifugeError> {
+/// How much rtp/rtcp classification relies on the udp port parity
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PortHeuristic {
+    /// payload only, for rtcp-mux and NAT remapped ports
+    Off,
+    /// payload first, port parity settles packets that fail to parse as rtcp
+    Fallback,
+    /// rtp only on even and rtcp only on odd ports
+    Strict,
+}
+
+impl FromStr for PortHeuristic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(PortHeuristic::Off),
+            "fallback" => Ok(PortHeuristic::Fallback),
+            "strict" => Ok(PortHeuristic::Strict),
+            x => bail!("Unknown port heuristic: {:?}", x),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Media {
+    Rtp,
+    Rtcp,
+}
+
+pub fn parse(remaining: &[u8], ports: PortHeuristic) -> Result<(udp::UdpHeader, UDP), CentrifugeError> {
     if let Ok((remaining, udp_hdr)) = udp::parse_udp_header(remaining) {
-        let inner = match extract(&udp_hdr, remaining) {
+        let inner = match extract(&udp_hdr, remaining, ports) {
             Ok(x) => x,
             Err(_) => unknown(remaining),
         };
@@ -20,17 +52,25 @@ pub fn parse(remaining: &[u8]) -> Result<(udp::UdpHeader, UDP), CentrifugeError>
 }
 
 #[inline]
-pub fn extract(udp_hdr: &UdpHeader, remaining: &[u8]) -> Result<UDP, CentrifugeError> {
+pub fn extract(udp_hdr: &UdpHeader, remaining: &[u8], ports: PortHeuristic) -> Result<UDP, CentrifugeError> {
     if remaining.is_empty() {
-        Ok(UDP::Binary(Vec::new()))
-    } else if is_rtp(udp_hdr, remaining) {
-        let rtp = rtp::extract(remaining)?;
-        Ok(UDP::Rtp(rtp))
-    } else if is_rtcp(udp_hdr, remaining) {
-        let rtcp = rtcp::extract(remaining)?;
-        Ok(UDP::Rtcp(rtcp))
-    } else {
-        Err(CentrifugeError::UnknownProtocol)
+        return Ok(UDP::Binary(Vec::new()));
+    }
+    match classify(udp_hdr, remaining, ports) {
+        Some(Media::Rtp) => Ok(UDP::Rtp(rtp::extract(remaining)?)),
+        Some(Media::Rtcp) => match rtcp::extract(remaining) {
+            Ok(rtcp) => Ok(UDP::Rtcp(rtcp)),
+            // legacy dynamic payload types 72-76 collide with rtcp, which
+            // only happens without rtcp-mux, where rtp sits on the even port
+            Err(_) if ports == PortHeuristic::Fallback
+                && udp_hdr.source_port.is_multiple_of(2)
+                && udp_hdr.dest_port.is_multiple_of(2) =>
+            {
+                Ok(UDP::Rtp(rtp::extract(remaining)?))
+            }
+            Err(err) => Err(err),
+        },
+        None => Err(CentrifugeError::UnknownProtocol),
     }
 }
 
@@ -49,37 +89,115 @@ pub fn unknown(remaining: &[u8]) -> UDP {
     }
 }
 
-fn is_rtp(udp_hdr: &UdpHeader, remaining: &[u8]) -> bool {
-    if udp_hdr.length < 12 {
-        return false;
+/// Classify by the first byte (RFC 7983) and the payload type range
+/// (RFC 5761). Port parity is only consulted as configured.
+fn classify(udp_hdr: &UdpHeader, remaining: &[u8], ports: PortHeuristic) -> Option<Media> {
+    if udp_hdr.length < 12 || remaining.len() < 2 {
+        return None;
     }
-    if udp_hdr.source_port % 2 == 0 && udp_hdr.dest_port % 2 == 0 {
-        let rtp_version = remaining[0] >> 6;
-        if rtp_version != 2 {
-            return false;
-        }
-        let rtp_payload = remaining[1] & 0x7f;
-        if rtp_payload < 64 || rtp_payload >= 96 {
-            return true;
-        }
+    // 128..=191 is rtp or rtcp, anything else is stun, dtls, turn channel data or zrtp
+    if remaining[0] < 128 || remaining[0] > 191 {
+        return None;
+    }
+    let even = udp_hdr.source_port.is_multiple_of(2) && udp_hdr.dest_port.is_multiple_of(2);
+    let odd = !udp_hdr.source_port.is_multiple_of(2) && !udp_hdr.dest_port.is_multiple_of(2);
+    let rtp_payload = remaining[1] & 0x7f;
+    let media = if (64..96).contains(&rtp_payload) {
+        Media::Rtcp
+    } else {
+        Media::Rtp
+    };
+    match (ports, media) {
+        (PortHeuristic::Strict, Media::Rtp) if !even => None,
+        (PortHeuristic::Strict, Media::Rtcp) if !odd => None,
+        _ => Some(media),
     }
-    false
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn is_rtcp(udp_hdr: &UdpHeader, remaining: &[u8]) -> bool {
-    if udp_hdr.length < 12 {
-        return false;
+    fn udp(source_port: u16, dest_port: u16, payload: &[u8]) -> UdpHeader {
+        UdpHeader {
+            source_port,
+            dest_port,
+            length: 8 + payload.len() as u16,
+            checksum: 0,
+        }
+    }
+
+    /// rtp with the given second byte, marker and payload type
+    fn rtp_pkt(b1: u8) -> Vec<u8> {
+        let mut pkt = vec![0x80, b1, 0x12, 0x34, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05];
+        pkt.extend_from_slice(&[0x55; 12]);
+        pkt
+    }
+
+    /// compound RR and empty SDES
+    fn rr_pkt() -> Vec<u8> {
+        vec![0x80, 201, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x80, 202, 0x00, 0x00]
+    }
+
+    fn classified(src: u16, dst: u16, pkt: &[u8], ports: PortHeuristic) -> Option<&'static str> {
+        classify(&udp(src, dst, pkt), pkt, ports).map(|m| match m {
+            Media::Rtp => "rtp",
+            Media::Rtcp => "rtcp",
+        })
     }
-    if udp_hdr.source_port % 2 == 1 && udp_hdr.dest_port % 2 == 1 {
-        let rtcp_version = remaining[0] >> 6;
-        if rtcp_version != 2 {
-            return false;
+
+    #[test]
+    fn first_byte_demux() {
+        let mut pkt = rtp_pkt(0x00);
+        // stun, dtls, turn channel data
+        for b in [0x00, 0x16, 0x40].iter() {
+            pkt[0] = *b;
+            assert_eq!(classified(5000, 6000, &pkt, PortHeuristic::Off), None);
         }
-        let rtp_payload = remaining[1] & 0x7f;
-        if rtp_payload >= 64 && rtp_payload < 96 {
-            return true;
+        pkt[0] = 0xc0;
+        assert_eq!(classified(5000, 6000, &pkt, PortHeuristic::Off), None);
+        pkt[0] = 0xbf;
+        assert_eq!(classified(5000, 6000, &pkt, PortHeuristic::Off), Some("rtp"));
+    }
+
+    #[test]
+    fn payload_type_demux() {
+        // RFC 5761: 64..96 is rtcp, including the marker bit
+        assert_eq!(classified(5000, 6000, &rtp_pkt(63), PortHeuristic::Off), Some("rtp"));
+        assert_eq!(classified(5000, 6000, &rtp_pkt(64), PortHeuristic::Off), Some("rtcp"));
+        assert_eq!(classified(5000, 6000, &rtp_pkt(0x80 | 95), PortHeuristic::Off), Some("rtcp"));
+        assert_eq!(classified(5000, 6000, &rtp_pkt(96), PortHeuristic::Off), Some("rtp"));
+        assert_eq!(classified(5000, 6000, &rtp_pkt(0x80 | 111), PortHeuristic::Off), Some("rtp"));
+    }
+
+    #[test]
+    fn port_heuristic_off_ignores_parity() {
+        assert_eq!(classified(5001, 6001, &rtp_pkt(111), PortHeuristic::Off), Some("rtp"));
+        assert_eq!(classified(5000, 6000, &rr_pkt(), PortHeuristic::Off), Some("rtcp"));
+    }
+
+    #[test]
+    fn port_heuristic_strict() {
+        assert_eq!(classified(5000, 6000, &rtp_pkt(111), PortHeuristic::Strict), Some("rtp"));
+        assert_eq!(classified(5001, 6001, &rtp_pkt(111), PortHeuristic::Strict), None);
+        assert_eq!(classified(5000, 6001, &rtp_pkt(111), PortHeuristic::Strict), None);
+        assert_eq!(classified(5001, 6001, &rr_pkt(), PortHeuristic::Strict), Some("rtcp"));
+        assert_eq!(classified(5000, 6000, &rr_pkt(), PortHeuristic::Strict), None);
+    }
+
+    #[test]
+    fn port_heuristic_fallback() {
+        // legacy rtp with PT 72 and the marker bit reads as an SR
+        let legacy = rtp_pkt(0x80 | 72);
+        match extract(&udp(5000, 6000, &legacy), &legacy, PortHeuristic::Fallback) {
+            Ok(UDP::Rtp(rtp)) => assert_eq!((rtp.header.payload, rtp.header.marker), (72, 1)),
+            _ => panic!("not rtp"),
         }
+        // only on even ports, and only when configured
+        assert!(extract(&udp(5001, 6001, &legacy), &legacy, PortHeuristic::Fallback).is_err());
+        assert!(extract(&udp(5000, 6000, &legacy), &legacy, PortHeuristic::Off).is_err());
+        // valid rtcp stays rtcp on even ports
+        let rr = rr_pkt();
+        assert!(matches!(extract(&udp(5000, 6000, &rr), &rr, PortHeuristic::Fallback), Ok(UDP::Rtcp(_))));
     }
-    false
 }
@@ -1,5 +1,9 @@
+use std::num::NonZeroU64;
 use structopt::StructOpt;
 use structopt::clap::{AppSettings, Shell};
+use crate::centrifuge::udp::PortHeuristic;
+use crate::flow::jitter_buffer::JitterBufferMode;
+use crate::flow::sdp::{ExtMapEntry, RtpMapEntry};
 
 #[derive(Debug, StructOpt)]
 #[structopt(global_settings = &[AppSettings::ColoredHelp])]
@@ -22,4 +26,37 @@ The default only shows few packets, this flag can be specified multiple times. (
     pub verbose: u8,
     #[structopt(short="n", long="threads", alias="cpus")]
     pub threads: Option<usize>,
+    /// Port parity use when telling rtp from rtcp: off, fallback or strict
+    #[structopt(long="port-heuristic", default_value="fallback")]
+    pub port_heuristic: PortHeuristic,
+    /// Header extension mapping as ID=URI, added to the a=extmap found in SDP
+    #[structopt(long="extmap", number_of_values=1)]
+    pub extmap: Vec<ExtMapEntry>,
+    /// Payload type mapping as PT=NAME[/RATE] (e.g. red, ulpfec, flexfec-03, pcmu/8000), added to the a=rtpmap found in SDP
+    #[structopt(long="rtpmap", number_of_values=1)]
+    pub rtpmap: Vec<RtpMapEntry>,
+    /// Jitter buffer emulation mode: fixed or adaptive
+    #[structopt(long="jb-mode", default_value="fixed")]
+    pub jb_mode: JitterBufferMode,
+    /// Fixed jitter buffer size in milliseconds
+    #[structopt(long="jb-size", default_value="60")]
+    pub jb_size: u64,
+    /// Adaptive jitter buffer minimum delay in milliseconds
+    #[structopt(long="jb-min-delay", default_value="20")]
+    pub jb_min_delay: u64,
+    /// Adaptive jitter buffer maximum delay in milliseconds
+    #[structopt(long="jb-max-delay", default_value="200")]
+    pub jb_max_delay: u64,
+    /// Flag streams whose sender clock drifts beyond this many ppm
+    #[structopt(long="skew-threshold", default_value="100")]
+    pub skew_threshold: f64,
+    /// Consider a stream ended after this many seconds without packets
+    #[structopt(long="idle-timeout", default_value="30")]
+    pub idle_timeout: u64,
+    /// Warn when a stream goes this many seconds without RTCP
+    #[structopt(long="rtcp-timeout", default_value="15")]
+    pub rtcp_timeout: u64,
+    /// Report per-interval statistics of every call, window length in seconds
+    #[structopt(long="interval")]
+    pub interval: Option<NonZeroU64>,
 }
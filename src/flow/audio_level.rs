@@ -0,0 +1,158 @@
+use std::{collections::HashMap, time::{Duration, SystemTime}};
+
+use super::{FlowPacket, sdp::SdpInfo};
+
+pub const SSRC_AUDIO_LEVEL: &str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+pub const CSRC_AUDIO_LEVEL: &str = "urn:ietf:params:rtp-hdrext:csrc-audio-level";
+/// levels louder than this many -dBov count as speech when the sender does
+/// not negotiate the voice activity flag
+const SPEECH_LEVEL: u8 = 50;
+/// a leg keeps speaking this long after its last speech packet
+const HANGOVER: Duration = Duration::from_millis(300);
+/// a stream this long without speech is considered silent, in packets
+const SILENT_PKTS: usize = 250;
+
+/// Extension ids of the audio level extensions, from the extmap
+#[derive(Clone, Copy)]
+pub struct LevelIds {
+    pub ssrc: Option<u8>,
+    pub csrc: Option<u8>,
+    /// the ssrc level extension was negotiated with `vad=on`
+    pub vad: bool,
+}
+
+impl LevelIds {
+    pub fn new(sdp: &SdpInfo) -> Self {
+        let ssrc = sdp.extension_id(SSRC_AUDIO_LEVEL);
+        let vad = ssrc
+            .and_then(|id| sdp.extmap_attributes.get(&id))
+            .is_some_and(|a| a.split_whitespace().any(|a| a == "vad=on"));
+        Self {
+            ssrc,
+            csrc: sdp.extension_id(CSRC_AUDIO_LEVEL),
+            vad,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelStats {
+    pub pkts: usize,
+    pub speaking_pkts: usize,
+    /// dBov, 0 is the loudest
+    pub loudest: i32,
+    pub quietest: i32,
+    pub avg: f64,
+}
+
+impl LevelStats {
+    fn update(&mut self, level: u8, speaking: bool) {
+        let dbov = -(level as i32);
+        if self.pkts == 0 {
+            self.loudest = dbov;
+            self.quietest = dbov;
+        }
+        self.loudest = self.loudest.max(dbov);
+        self.quietest = self.quietest.min(dbov);
+        self.avg += (dbov as f64 - self.avg) / (self.pkts + 1) as f64;
+        self.pkts += 1;
+        if speaking {
+            self.speaking_pkts += 1;
+        }
+    }
+    pub fn speaking_rate(&self) -> f64 {
+        if self.pkts == 0 {
+            return 0.0;
+        }
+        self.speaking_pkts as f64 / self.pkts as f64 * 100.0
+    }
+    /// kept sending for a while without ever speaking
+    pub fn silent(&self) -> bool {
+        self.pkts >= SILENT_PKTS && self.speaking_pkts == 0
+    }
+}
+
+/// RFC 6464 sender level and RFC 6465 mixer levels of one stream
+pub struct AudioLevelStats {
+    pub level: LevelStats,
+    /// csrc => level
+    pub contributors: HashMap<u32, LevelStats>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Speaker {
+    Nobody,
+    Peer1,
+    Peer2,
+    Both,
+}
+
+/// RFC 6464: voice activity and level in -dBov. The V bit only means
+/// something with `vad=on`, otherwise speech is judged by the level.
+fn ssrc_level(pkt: &FlowPacket, ids: LevelIds) -> Option<(bool, u8)> {
+    let data = pkt.rtp()?.header.extension(ids.ssrc?)?;
+    let b = *data.first()?;
+    let level = b & 0x7f;
+    let speaking = if ids.vad { b & 0x80 != 0 } else { level < SPEECH_LEVEL };
+    Some((speaking, level))
+}
+
+pub fn analyze(pkts: &[FlowPacket], ids: LevelIds) -> Option<AudioLevelStats> {
+    let mut level = LevelStats::default();
+    let mut contributors: HashMap<u32, LevelStats> = HashMap::new();
+    for p in pkts.iter() {
+        if let Some((speaking, l)) = ssrc_level(p, ids) {
+            level.update(l, speaking);
+        }
+        // RFC 6465: one level per csrc, in csrc list order
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        if let Some(data) = ids.csrc.and_then(|id| rtp.header.extension(id)) {
+            for (csrc, b) in rtp.header.csrcs.iter().zip(data.iter()) {
+                let l = b & 0x7f;
+                contributors.entry(*csrc).or_default().update(l, l < SPEECH_LEVEL);
+            }
+        }
+    }
+    if level.pkts == 0 && contributors.is_empty() {
+        return None;
+    }
+    Some(AudioLevelStats { level, contributors })
+}
+
+/// Who is speaking over the call, one entry every time it changes.
+pub fn speaker_timeline(peer1_pkts: &[FlowPacket], peer2_pkts: &[FlowPacket], ids: LevelIds) -> Vec<(SystemTime, Speaker)> {
+    let mut events: Vec<(SystemTime, bool, bool)> = Vec::new();
+    for (pkts, is_peer1) in [(peer1_pkts, true), (peer2_pkts, false)].iter() {
+        for p in pkts.iter() {
+            if let Some((speaking, _)) = ssrc_level(p, ids) {
+                events.push((p.ts, *is_peer1, speaking));
+            }
+        }
+    }
+    events.sort_by_key(|e| e.0);
+
+    let mut timeline: Vec<(SystemTime, Speaker)> = Vec::new();
+    let mut last_speech: [Option<SystemTime>; 2] = [None, None];
+    for (ts, is_peer1, speaking) in events.into_iter() {
+        if speaking {
+            last_speech[if is_peer1 { 0 } else { 1 }] = Some(ts);
+        }
+        let active = |i: usize| match last_speech[i] {
+            Some(last) => ts.duration_since(last).unwrap_or_default() <= HANGOVER,
+            None => false,
+        };
+        let speaker = match (active(0), active(1)) {
+            (true, true) => Speaker::Both,
+            (true, false) => Speaker::Peer1,
+            (false, true) => Speaker::Peer2,
+            (false, false) => Speaker::Nobody,
+        };
+        if timeline.last().map(|s| s.1) != Some(speaker) {
+            timeline.push((ts, speaker));
+        }
+    }
+    timeline
+}
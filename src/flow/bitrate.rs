@@ -0,0 +1,262 @@
+use std::{collections::HashMap, net::SocketAddr, time::{Duration, SystemTime}};
+
+use crate::structs::rtcp::{PayloadFeedback, RtcpPacket, TransportFeedback};
+
+use super::{
+    FlowDirection, FlowPacket,
+    connection::Connection,
+    participant,
+    transport::{self, TransportIds},
+};
+
+const WINDOW: Duration = Duration::from_secs(1);
+/// ip and udp headers
+const UDP_IP_OVERHEAD: u64 = 28;
+/// twcc receive delta unit, usec
+const TWCC_DELTA_UNIT: u64 = 250;
+/// sending above this share of the estimate is an overshoot
+const OVERSHOOT_RATIO: f64 = 1.1;
+/// sending below this share of the estimate is underuse
+const UNDERUSE_RATIO: f64 = 0.5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EstimateUsage {
+    Unknown,
+    Within,
+    Overshoot,
+    Underuse,
+}
+pub struct BitrateWindow {
+    pub start: SystemTime,
+    /// bps
+    pub payload: u64,
+    /// rtp, udp and ip headers, bps
+    pub overhead: u64,
+    /// padding-only packets on the wire, left out of the total, bps
+    pub padding: u64,
+    /// latest REMB covering the stream
+    pub remb: Option<u64>,
+    /// rate the receiver acknowledged in TWCC feedback
+    pub twcc: Option<u64>,
+    pub usage: EstimateUsage,
+}
+
+impl BitrateWindow {
+    fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            payload: 0,
+            overhead: 0,
+            padding: 0,
+            remb: None,
+            twcc: None,
+            usage: EstimateUsage::Unknown,
+        }
+    }
+    pub fn total(&self) -> u64 {
+        self.payload + self.overhead
+    }
+    /// REMB when the receiver sends it, the acknowledged rate otherwise
+    pub fn estimate(&self) -> Option<u64> {
+        self.remb.or(self.twcc)
+    }
+    fn classify(&mut self, estimate: Option<u64>) {
+        self.usage = match estimate {
+            Some(e) if self.total() as f64 > e as f64 * OVERSHOOT_RATIO => EstimateUsage::Overshoot,
+            Some(e) if (self.total() as f64) < e as f64 * UNDERUSE_RATIO => EstimateUsage::Underuse,
+            Some(_) => EstimateUsage::Within,
+            None => EstimateUsage::Unknown,
+        };
+    }
+}
+pub struct StreamBitrate {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub windows: Vec<BitrateWindow>,
+}
+pub struct ParticipantBitrate {
+    pub cname: String,
+    pub windows: Vec<BitrateWindow>,
+}
+
+/// Consecutive windows with the same usage merged into (start, end, usage).
+pub fn usage_periods(windows: &[BitrateWindow]) -> Vec<(SystemTime, SystemTime, EstimateUsage)> {
+    let mut periods: Vec<(SystemTime, SystemTime, EstimateUsage)> = Vec::new();
+    for w in windows.iter() {
+        let end = w.start + WINDOW;
+        match periods.last_mut() {
+            Some(p) if p.2 == w.usage && p.1 == w.start => p.1 = end,
+            _ => periods.push((w.start, end, w.usage)),
+        }
+    }
+    periods
+}
+
+/// Send bitrate of every stream and every participant in 1 second windows
+/// aligned to the capture start, next to the receivers' bandwidth estimates.
+pub fn compute(conn_list: &[Connection], ids: TransportIds) -> (Vec<StreamBitrate>, Vec<ParticipantBitrate>) {
+    let start = match conn_list.iter().filter_map(|c| c.ingress_tsrange()).map(|r| r.0).min() {
+        Some(start) => start,
+        None => return (vec![], vec![]),
+    };
+    let mut streams = Vec::new();
+    for conn in conn_list.iter() {
+        // the receiver's feedback flows the other way
+        let list = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts, &conn.egress_rtcp),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts, &conn.ingress_rtcp),
+        ];
+        for (ssrc, dir, pkts, feedback) in list.iter() {
+            let ssrc = match ssrc {
+                Some(ssrc) => *ssrc,
+                None => continue,
+            };
+            let windows = stream_windows(start, ssrc, pkts, feedback, ids);
+            streams.push(StreamBitrate {
+                ssrc,
+                dir: *dir,
+                remote: conn.header.remote,
+                windows,
+            });
+        }
+    }
+
+    let mut participants = Vec::new();
+    for p in participant::extract_participants(conn_list).iter() {
+        let mut windows: Vec<BitrateWindow> = Vec::new();
+        for ps in p.streams.iter() {
+            let s = streams
+                .iter()
+                .find(|s| s.ssrc == ps.ssrc && s.dir == ps.dir && s.remote == ps.remote);
+            let s = match s {
+                Some(s) => s,
+                None => continue,
+            };
+            for (i, w) in s.windows.iter().enumerate() {
+                if windows.len() <= i {
+                    windows.push(BitrateWindow::new(w.start));
+                }
+                let sum = &mut windows[i];
+                sum.payload += w.payload;
+                sum.overhead += w.overhead;
+                sum.padding += w.padding;
+                // a REMB covers all streams of the sender, the acknowledged
+                // rate is per stream
+                sum.remb = sum.remb.max(w.remb);
+                sum.twcc = match (sum.twcc, w.twcc) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        windows.iter_mut().for_each(|w| w.classify(w.estimate()));
+        participants.push(ParticipantBitrate {
+            cname: p.cname.clone(),
+            windows,
+        });
+    }
+    (streams, participants)
+}
+
+fn stream_windows(
+    start: SystemTime,
+    ssrc: u32,
+    pkts: &[FlowPacket],
+    feedback: &[FlowPacket],
+    ids: TransportIds,
+) -> Vec<BitrateWindow> {
+    let index = |ts: SystemTime| (ts.duration_since(start).unwrap_or_default().as_micros() / WINDOW.as_micros()) as usize;
+    let count = match pkts.last() {
+        Some(p) => index(p.ts) + 1,
+        None => return vec![],
+    };
+    let mut windows: Vec<BitrateWindow> = (0..count)
+        .map(|i| BitrateWindow::new(start + WINDOW * i as u32))
+        .collect();
+    let mut bytes = vec![(0u64, 0u64, 0u64); count];
+    for p in pkts.iter() {
+        if let Some(rtp) = p.rtp() {
+            let b = &mut bytes[index(p.ts)];
+            if rtp.padding_only() {
+                b.2 += rtp.len as u64 + UDP_IP_OVERHEAD;
+                continue;
+            }
+            b.0 += rtp.payload.len() as u64;
+            b.1 += (rtp.len - rtp.payload.len()) as u64 + UDP_IP_OVERHEAD;
+        }
+    }
+
+    // estimates as they arrive, REMB as (bitrate, covers only this stream)
+    let mut remb: Vec<Option<(u64, bool)>> = vec![None; count];
+    let mut twcc: Vec<(u64, u64)> = vec![(0, 0); count];
+    let mut sizes: HashMap<u16, u64> = HashMap::new();
+    let mut cursor = 0;
+    for p in feedback.iter() {
+        let rtcp = match p.rtcp() {
+            Some(rtcp) => rtcp,
+            None => continue,
+        };
+        let i = index(p.ts);
+        if i >= count {
+            break;
+        }
+        while cursor < pkts.len() && pkts[cursor].ts <= p.ts {
+            if let (Some(seq), Some(rtp)) = (transport::twcc_seq(&pkts[cursor], ids), pkts[cursor].rtp()) {
+                sizes.insert(seq, rtp.len as u64 + UDP_IP_OVERHEAD);
+            }
+            cursor += 1;
+        }
+        for packet in rtcp.packets.iter() {
+            match packet {
+                RtcpPacket::PayloadFeedback(PayloadFeedback::Remb(r)) if r.ssrcs.contains(&ssrc) => {
+                    remb[i] = Some((r.bitrate, r.ssrcs.len() == 1));
+                }
+                RtcpPacket::TransportFeedback(TransportFeedback::TransportCc(fb)) => {
+                    // bytes of this stream received over the arrival span of
+                    // the whole feedback, deltas chain through the packets of
+                    // every stream and the first is relative to the reference time
+                    let mut received = 0;
+                    let mut span = 0;
+                    let mut first = true;
+                    let mut covered = false;
+                    for status in fb.packets.iter() {
+                        covered |= sizes.contains_key(&status.seq);
+                        let delta = match status.delta {
+                            Some(delta) => delta,
+                            None => continue,
+                        };
+                        if !first {
+                            span += delta.max(0) as u64 * TWCC_DELTA_UNIT;
+                            received += sizes.get(&status.seq).copied().unwrap_or(0);
+                        }
+                        first = false;
+                    }
+                    // feedback not covering the stream says nothing about it
+                    if covered && span > 0 {
+                        twcc[i].0 += received * 8 * 1_000_000 / span;
+                        twcc[i].1 += 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut last_remb = None;
+    for (i, w) in windows.iter_mut().enumerate() {
+        let secs = WINDOW.as_secs_f64();
+        w.payload = (bytes[i].0 as f64 * 8.0 / secs) as u64;
+        w.overhead = (bytes[i].1 as f64 * 8.0 / secs) as u64;
+        w.padding = (bytes[i].2 as f64 * 8.0 / secs) as u64;
+        last_remb = remb[i].or(last_remb);
+        w.remb = last_remb.map(|r| r.0);
+        w.twcc = twcc[i].0.checked_div(twcc[i].1);
+        // a REMB shared with other streams is judged per participant
+        match last_remb {
+            Some((_, true)) => w.classify(w.estimate()),
+            _ => w.classify(w.twcc),
+        }
+    }
+    windows
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use super::{
+    FlowDirection, FlowPacket,
+    call::{DEFAULT_CLOCK_RATE, InterarrivalJitter, seq_delta},
+    connection::{self, ConnectionMap},
+    sdp::SdpInfo,
+};
+
+/// One SSRC inside a BUNDLE 5-tuple
+pub struct BundleStream {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub mid: Option<String>,
+    pub pt: u8,
+    /// encoding name from the rtpmap, "rtx", "flexfec" etc.
+    pub codec: Option<String>,
+    pub pkts: usize,
+    pub bytes: usize,
+    pub lost: i64,
+    pub loss_rate: f64,
+    pub jitter: u64,
+}
+pub struct Bundle {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub streams: Vec<BundleStream>,
+}
+
+/// Demultiplex every 5-tuple carrying more than one SSRC into its streams,
+/// by SSRC and MID, with per stream statistics. Streams without a MID of
+/// their own take the one seen for their SSRC in the other direction.
+pub fn analyze(conn_map: &ConnectionMap, sdp: &SdpInfo) -> Vec<Bundle> {
+    let mut bundles = Vec::new();
+    for (key, list) in conn_map.map.iter() {
+        let mut by_ssrc: HashMap<(FlowDirection, u32), Vec<&FlowPacket>> = HashMap::new();
+        for conn in list.iter() {
+            let pkts = [(FlowDirection::Ingress, &conn.ingress_pkts), (FlowDirection::Egress, &conn.egress_pkts)];
+            for (dir, pkts) in pkts.iter() {
+                for p in pkts.iter() {
+                    if let Some(rtp) = p.rtp() {
+                        by_ssrc.entry((*dir, rtp.header.ssrc)).or_default().push(p);
+                    }
+                }
+            }
+        }
+        if by_ssrc.len() < 2 {
+            continue;
+        }
+        let mut streams: Vec<BundleStream> = by_ssrc
+            .into_iter()
+            .map(|((dir, ssrc), mut pkts)| {
+                pkts.sort_by_key(|p| p.ts);
+                measure(dir, ssrc, &pkts, conn_map.mid_id, sdp)
+            })
+            .collect();
+        let mids: HashMap<u32, String> = streams
+            .iter()
+            .filter_map(|s| Some((s.ssrc, s.mid.clone()?)))
+            .collect();
+        for s in streams.iter_mut() {
+            if s.mid.is_none() {
+                s.mid = mids.get(&s.ssrc).cloned();
+            }
+        }
+        streams.sort_by(|a, b| (a.dir != FlowDirection::Ingress, &a.mid, a.ssrc).cmp(&(b.dir != FlowDirection::Ingress, &b.mid, b.ssrc)));
+        bundles.push(Bundle {
+            local: list[0].header.local,
+            remote: key.remote,
+            streams,
+        });
+    }
+    bundles.sort_by_key(|b| b.remote);
+    bundles
+}
+
+fn measure(dir: FlowDirection, ssrc: u32, pkts: &[&FlowPacket], mid_id: Option<u8>, sdp: &SdpInfo) -> BundleStream {
+    let clock_rate = pkts
+        .first()
+        .and_then(|p| sdp.clock_rate(p.rtp()?.header.payload))
+        .unwrap_or(DEFAULT_CLOCK_RATE);
+    let mut jitter = InterarrivalJitter::new(clock_rate);
+    let mut mid = None;
+    let mut pt = 0;
+    let mut bytes = 0;
+    let mut first: Option<i64> = None;
+    let mut last: Option<i64> = None;
+    let mut highest = i64::MIN;
+    for p in pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        if mid.is_none() {
+            mid = connection::mid(rtp, mid_id);
+        }
+        pt = rtp.header.payload;
+        bytes += rtp.len;
+        let seq = match last {
+            Some(last) => last + seq_delta(rtp.header.seqnum, last as u16),
+            None => rtp.header.seqnum as i64,
+        };
+        first = Some(first.map_or(seq, |f: i64| f.min(seq)));
+        highest = highest.max(seq);
+        last = Some(seq);
+        jitter.update(p);
+    }
+    let expected = first.map_or(0, |f| highest - f + 1);
+    let lost = (expected - pkts.len() as i64).max(0);
+    BundleStream {
+        ssrc,
+        dir,
+        mid,
+        pt,
+        codec: sdp.rtpmap.get(&pt).cloned(),
+        pkts: pkts.len(),
+        bytes,
+        lost,
+        loss_rate: if expected > 0 {
+            lost as f64 / expected as f64 * 100.0
+        } else {
+            0.0
+        },
+        jitter: jitter.value(),
+    }
+}
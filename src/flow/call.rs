@@ -1,7 +1,20 @@
 use crate::flow::{FlowPayload, FlowType};
-use std::{collections::HashSet, net::SocketAddr, time::SystemTime};
+use crate::structs::rtcp::VoipMetrics;
+use std::{collections::HashSet, net::SocketAddr, time::{Duration, SystemTime}};
 
-use super::{FlowPacket, connection::{Connection, ConnectionHeader, ConnectionMap}};
+use super::{
+    FlowPacket,
+    audio_level::{self, AudioLevelStats, LevelIds, Speaker},
+    connection::{Connection, ConnectionHeader, ConnectionMap},
+    feedback::{self, FeedbackStats},
+    jitter_buffer::{self, JitterBufferConfig, JitterBufferStats},
+    reception::{self, ReceptionComparison},
+    rtt::{self, RttStats},
+    skew::{self, SkewStats},
+    timestamp::{self, MediaClock, TimestampStats},
+    transport::{self, DelayVariationStats, TransportIds, TwccComparison},
+    window::{self, WindowStats},
+};
 
 pub struct CallHeader {
     pub peer1_ssrc: u32,
@@ -17,9 +30,9 @@ pub struct Call {
 impl Call {
     pub fn compute_stats(&self) -> CallStats {
         let peer1_delay = compute_delay_stats(&self.peer1.ingress_pkts, &self.peer2.egress_pkts);
-        let peer1_ingress_flow = compute_flow_stats(&self.peer1.ingress_pkts);
+        let peer1_ingress_flow = compute_flow_stats(&self.peer1.ingress_pkts, self.peer1.ingress_clock_rate);
         let peer2_delay = compute_delay_stats(&self.peer2.ingress_pkts, &self.peer1.egress_pkts);
-        let peer2_ingress_flow = compute_flow_stats(&self.peer2.ingress_pkts);
+        let peer2_ingress_flow = compute_flow_stats(&self.peer2.ingress_pkts, self.peer2.ingress_clock_rate);
         CallStats {
             peer1_delay,
             peer1_ingress_flow,
@@ -27,6 +40,96 @@ impl Call {
             peer2_ingress_flow,
         }
     }
+    pub fn emulate_jitter_buffer(&self, config: &JitterBufferConfig) -> CallJitterBufferStats {
+        CallJitterBufferStats {
+            peer1_ingress: jitter_buffer::emulate(&self.peer1.ingress_pkts, self.peer1.ingress_clock_rate, config),
+            peer2_ingress: jitter_buffer::emulate(&self.peer2.ingress_pkts, self.peer2.ingress_clock_rate, config),
+        }
+    }
+    pub fn estimate_skew(&self, threshold_ppm: f64) -> CallSkewStats {
+        CallSkewStats {
+            peer1_ingress: skew::estimate_skew(&self.peer1.ingress_pkts, self.peer1.ingress_clock_rate, threshold_ppm),
+            peer2_ingress: skew::estimate_skew(&self.peer2.ingress_pkts, self.peer2.ingress_clock_rate, threshold_ppm),
+        }
+    }
+    pub fn detect_timestamp_anomalies(&self) -> CallTimestampStats {
+        CallTimestampStats {
+            peer1_ingress: timestamp::detect_anomalies(&self.peer1.ingress_pkts, self.peer1.ingress_clock_rate),
+            peer2_ingress: timestamp::detect_anomalies(&self.peer2.ingress_pkts, self.peer2.ingress_clock_rate),
+        }
+    }
+    /// round-trip time of each leg, between capture point and remote endpoint
+    pub fn compute_rtt(&self) -> CallRttStats {
+        CallRttStats {
+            peer1: rtt::compute_rtt(&self.peer1.egress_rtcp, &self.peer1.ingress_rtcp),
+            peer2: rtt::compute_rtt(&self.peer2.egress_rtcp, &self.peer2.ingress_rtcp),
+        }
+    }
+    /// reception reports of each peer against the stream forwarded to it
+    pub fn compare_reception_reports(&self) -> CallReceptionStats {
+        let compare = |peer: &Connection, other: &Connection| {
+            reception::compare(&peer.ingress_rtcp, &peer.egress_pkts, &other.ingress_pkts, peer.egress_clock_rate)
+        };
+        CallReceptionStats {
+            peer1: compare(&self.peer1, &self.peer2),
+            peer2: compare(&self.peer2, &self.peer1),
+        }
+    }
+    /// RTCP XR VoIP metrics each peer reported about the stream forwarded to it
+    pub fn endpoint_voip_metrics(&self) -> CallVoipMetrics {
+        let metrics = |peer: &Connection| {
+            reception::last_voip_metrics(&peer.ingress_rtcp, peer.egress_ssrc()?)
+        };
+        CallVoipMetrics {
+            peer1: metrics(&self.peer1),
+            peer2: metrics(&self.peer2),
+        }
+    }
+    /// RTCP feedback each peer sent, and the capture point sent to it
+    pub fn collect_feedback(&self) -> CallFeedbackStats {
+        CallFeedbackStats {
+            from_peer1: feedback::collect(&self.peer1.ingress_rtcp),
+            to_peer1: feedback::collect(&self.peer1.egress_rtcp),
+            from_peer2: feedback::collect(&self.peer2.ingress_rtcp),
+            to_peer2: feedback::collect(&self.peer2.egress_rtcp),
+        }
+    }
+    /// audio level of each direction and who speaks when
+    pub fn track_audio_levels(&self, ids: LevelIds) -> CallAudioLevelStats {
+        CallAudioLevelStats {
+            peer1_ingress: audio_level::analyze(&self.peer1.ingress_pkts, ids),
+            peer2_ingress: audio_level::analyze(&self.peer2.ingress_pkts, ids),
+            speakers: audio_level::speaker_timeline(&self.peer1.ingress_pkts, &self.peer2.ingress_pkts, ids),
+        }
+    }
+    /// abs-send-time delay variation of what each peer sends, and TWCC
+    /// feedback about each leg against the packets seen at the capture point
+    pub fn analyze_transport(&self, ids: TransportIds) -> CallTransportStats {
+        CallTransportStats {
+            peer1_ingress: transport::delay_variation(&self.peer1.ingress_pkts, ids),
+            peer2_ingress: transport::delay_variation(&self.peer2.ingress_pkts, ids),
+            to_peer1: transport::compare(&self.peer1.ingress_pkts, &self.peer1.egress_rtcp, ids),
+            from_peer1: transport::compare(&self.peer1.egress_pkts, &self.peer1.ingress_rtcp, ids),
+            to_peer2: transport::compare(&self.peer2.ingress_pkts, &self.peer2.egress_rtcp, ids),
+            from_peer2: transport::compare(&self.peer2.egress_pkts, &self.peer2.ingress_rtcp, ids),
+        }
+    }
+    pub fn compute_window_stats(&self, interval: Duration) -> CallWindowStats {
+        CallWindowStats {
+            peer1_ingress: window::compute_window_stats(
+                &self.peer1.ingress_pkts,
+                &self.peer2.egress_pkts,
+                self.peer1.ingress_clock_rate,
+                interval,
+            ),
+            peer2_ingress: window::compute_window_stats(
+                &self.peer2.ingress_pkts,
+                &self.peer1.egress_pkts,
+                self.peer2.ingress_clock_rate,
+                interval,
+            ),
+        }
+    }
 }
 pub struct DelayStats {
     pub max: u64,
@@ -45,6 +148,54 @@ pub struct CallStats {
     pub peer2_delay: DelayStats,
     pub peer2_ingress_flow: FlowStats,
 }
+pub struct CallJitterBufferStats {
+    pub peer1_ingress: JitterBufferStats,
+    pub peer2_ingress: JitterBufferStats,
+}
+pub struct CallSkewStats {
+    pub peer1_ingress: Option<SkewStats>,
+    pub peer2_ingress: Option<SkewStats>,
+}
+pub struct CallTimestampStats {
+    pub peer1_ingress: TimestampStats,
+    pub peer2_ingress: TimestampStats,
+}
+pub struct CallRttStats {
+    pub peer1: Option<RttStats>,
+    pub peer2: Option<RttStats>,
+}
+pub struct CallReceptionStats {
+    pub peer1: Option<ReceptionComparison>,
+    pub peer2: Option<ReceptionComparison>,
+}
+pub struct CallVoipMetrics {
+    pub peer1: Option<VoipMetrics>,
+    pub peer2: Option<VoipMetrics>,
+}
+pub struct CallFeedbackStats {
+    pub from_peer1: FeedbackStats,
+    pub to_peer1: FeedbackStats,
+    pub from_peer2: FeedbackStats,
+    pub to_peer2: FeedbackStats,
+}
+pub struct CallAudioLevelStats {
+    pub peer1_ingress: Option<AudioLevelStats>,
+    pub peer2_ingress: Option<AudioLevelStats>,
+    pub speakers: Vec<(SystemTime, Speaker)>,
+}
+pub struct CallTransportStats {
+    pub peer1_ingress: Option<DelayVariationStats>,
+    pub peer2_ingress: Option<DelayVariationStats>,
+    /// twcc feedback sent to / by each peer
+    pub to_peer1: Option<TwccComparison>,
+    pub from_peer1: Option<TwccComparison>,
+    pub to_peer2: Option<TwccComparison>,
+    pub from_peer2: Option<TwccComparison>,
+}
+pub struct CallWindowStats {
+    pub peer1_ingress: Vec<WindowStats>,
+    pub peer2_ingress: Vec<WindowStats>,
+}
 
 pub fn extract_calls(conn_list: &Vec<Connection>) -> Vec<Call> {
     let mut mark_set = HashSet::with_capacity(conn_list.len());
@@ -97,12 +248,10 @@ pub fn extract_calls(conn_list: &Vec<Connection>) -> Vec<Call> {
     }
     calls
 }
-fn compute_delay_stats(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket]) -> DelayStats {
-    let mut n = 0u64;
+/// match ingress packets to forwarded egress packets, return (ingress ts, delay usec)
+pub fn match_delays(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket]) -> Vec<(SystemTime, u64)> {
+    let mut delays = Vec::with_capacity(ingress_pkts.len());
     let mut ec = 0usize;
-    let mut dtotal = 0u64;
-    let mut dtotal_square = 0u128;
-    let mut dmax = 0u64;
     for pkt1 in ingress_pkts {
         let mut ec_adv = 0usize;
         let mut i = 0usize;
@@ -115,12 +264,7 @@ fn compute_delay_stats(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket])
                 if let FlowPayload::Rtp(ref p2) = pkt2.payload {
                     if p1.header.seqnum == p2.header.seqnum {
                         let d = pkt2.ts.duration_since(pkt1.ts).unwrap().as_micros() as u64;
-                        n += 1;
-                        if dmax < d {
-                            dmax = d;
-                        }
-                        dtotal += d;
-                        dtotal_square += d as u128 * d as u128;
+                        delays.push((pkt1.ts, d));
                         break;
                     }
                 }
@@ -132,6 +276,21 @@ fn compute_delay_stats(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket])
         }
         ec += ec_adv;
     }
+    delays
+}
+fn compute_delay_stats(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket]) -> DelayStats {
+    let mut n = 0u64;
+    let mut dtotal = 0u64;
+    let mut dtotal_square = 0u128;
+    let mut dmax = 0u64;
+    for (_, d) in match_delays(ingress_pkts, egress_pkts) {
+        n += 1;
+        if dmax < d {
+            dmax = d;
+        }
+        dtotal += d;
+        dtotal_square += d as u128 * d as u128;
+    }
     let davg = dtotal / n;
     let dstd = (dtotal_square - dtotal as u128 * dtotal as u128 / n as u128) as f64 / n as f64;
     DelayStats {
@@ -140,7 +299,7 @@ fn compute_delay_stats(ingress_pkts: &[FlowPacket], egress_pkts: &[FlowPacket])
         std: dstd,
     }
 }
-fn compute_flow_stats(rx_pkts: &[FlowPacket]) -> FlowStats {
+fn compute_flow_stats(rx_pkts: &[FlowPacket], clock_rate: u32) -> FlowStats {
     let mut n = 0u64;
     let mut ec = 0usize;
     let mut dtotal = 0u64;
@@ -181,8 +340,16 @@ fn compute_flow_stats(rx_pkts: &[FlowPacket]) -> FlowStats {
             -((r1 - r2) as i64)
         };
         let sd = timestamp_delta(pkt2.rtp().unwrap().header.timestamp, pkt1.rtp().unwrap().header.timestamp);
-        let sd = sd * 1000 / 48; // convert to usec
-        
+        let sd = timestamp_to_usec(sd, clock_rate);
+        if timestamp::is_jump(sd, rd) {
+            // timestamp discontinuity, only the sequence gap tells the loss
+            let gap = seq_delta(pkt2.rtp().unwrap().header.seqnum, pkt1.rtp().unwrap().header.seqnum);
+            if gap > 1 {
+                lost_pkts += gap as u64 - 1;
+            }
+            continue;
+        }
+
         let delta = rd - sd;
         if delta.abs() > max_delta.abs() {
             max_delta = delta;
@@ -206,6 +373,35 @@ fn compute_flow_stats(rx_pkts: &[FlowPacket]) -> FlowStats {
         max_inter_frame_delay,
     }
 }
+/// RFC 3550 interarrival jitter, usec
+pub struct InterarrivalJitter {
+    clock: MediaClock,
+    last: Option<(i64, i64)>,
+    jitter: f64,
+}
+
+impl InterarrivalJitter {
+    pub fn new(clock_rate: u32) -> Self {
+        Self {
+            clock: MediaClock::new(clock_rate),
+            last: None,
+            jitter: 0.0,
+        }
+    }
+    pub fn update(&mut self, pkt: &FlowPacket) -> u64 {
+        if let Some((arrival, media)) = self.clock.update(pkt) {
+            if let Some((last_arrival, last_media)) = self.last {
+                let d = (arrival - last_arrival - (media - last_media)).abs() as f64;
+                self.jitter += (d - self.jitter) / 16.0;
+            }
+            self.last = Some((arrival, media));
+        }
+        self.value()
+    }
+    pub fn value(&self) -> u64 {
+        self.jitter as u64
+    }
+}
 /// return a >= b
 fn cseq_greater(a: u16, b: u16) -> bool {
     if a >= b {
@@ -217,7 +413,11 @@ fn cseq_greater(a: u16, b: u16) -> bool {
     }
 }
 /// return a - b
-fn timestamp_delta(a: u32, b: u32) -> i64 {
+pub fn seq_delta(a: u16, b: u16) -> i64 {
+    a.wrapping_sub(b) as i16 as i64
+}
+/// return a - b
+pub fn timestamp_delta(a: u32, b: u32) -> i64 {
     if a >= b {
         let d = a - b;
         if d < 0x8000_0000 {
@@ -236,4 +436,10 @@ fn timestamp_delta(a: u32, b: u32) -> i64 {
         }
     }
 
-}
\ No newline at end of file
+}
+/// clock rate of streams whose payload type and sender reports don't tell
+pub const DEFAULT_CLOCK_RATE: u32 = 48_000;
+/// convert rtp timestamp units to usec
+pub fn timestamp_to_usec(delta: i64, clock_rate: u32) -> i64 {
+    delta * 1_000_000 / clock_rate as i64
+}
@@ -0,0 +1,136 @@
+use std::{collections::{HashMap, HashSet}, net::SocketAddr, time::{Duration, SystemTime}};
+
+use super::{FlowDirection, connection::Connection};
+
+/// ip and udp headers, counted into both rtp and rtcp bandwidth
+const UDP_IP_OVERHEAD: u64 = 28;
+/// RFC 3550 section 6.2 recommended rtcp fraction of the session bandwidth, percent
+pub const RTCP_SHARE_LIMIT: f64 = 5.0;
+
+/// RTCP behaviour of the endpoint sending one rtp stream
+pub struct RtcpConformance {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    /// compound packets sent by the ssrc
+    pub rtcp_pkts: usize,
+    /// interval between compound packets, usec
+    pub interval_min: Option<u64>,
+    pub interval_avg: Option<u64>,
+    pub interval_max: Option<u64>,
+    pub rtp_bytes: u64,
+    pub rtcp_bytes: u64,
+    /// rtcp percent of the bandwidth the ssrc sends
+    pub rtcp_share: f64,
+    /// sent media without ever sending a sender report
+    pub missing_sr: bool,
+    /// the receiver never reported on the stream
+    pub missing_rr: bool,
+    /// periods of the stream without rtcp longer than the timeout, (start, usec)
+    pub silent_periods: Vec<(SystemTime, u64)>,
+}
+
+impl RtcpConformance {
+    pub fn flooding(&self) -> bool {
+        self.rtcp_share > RTCP_SHARE_LIMIT
+    }
+}
+
+#[derive(Default)]
+struct RtcpSender {
+    /// (capture ts, compound size)
+    pkts: Vec<(SystemTime, usize)>,
+    sr: bool,
+}
+
+/// Check every rtp stream's RTCP against RFC 3550: how often and how much is
+/// sent, whether SRs and RRs exist, and how long it stayed silent.
+pub fn check(conn_list: &[Connection], timeout: Duration) -> Vec<RtcpConformance> {
+    // an sfu relays rtcp, each leg is its own sender to not count reports twice
+    let mut senders: HashMap<(SocketAddr, u32, FlowDirection), RtcpSender> = HashMap::new();
+    let mut reported: HashSet<u32> = HashSet::new();
+    for conn in conn_list.iter() {
+        let rtcp_pkts = [(FlowDirection::Ingress, &conn.ingress_rtcp), (FlowDirection::Egress, &conn.egress_rtcp)];
+        for (dir, pkts) in rtcp_pkts.iter() {
+            for p in pkts.iter() {
+                let rtcp = match p.rtcp() {
+                    Some(rtcp) => rtcp,
+                    None => continue,
+                };
+                // a compound packet starts with the SR or RR of its sender
+                let sender = senders.entry((conn.header.remote, rtcp.header.ssrc, *dir)).or_default();
+                sender.pkts.push((p.ts, rtcp.len));
+                sender.sr |= rtcp.sender_reports().next().is_some();
+                reported.extend(rtcp.report_blocks().map(|(_, block)| block.ssrc));
+            }
+        }
+    }
+    for sender in senders.values_mut() {
+        sender.pkts.sort_by_key(|p| p.0);
+    }
+
+    let empty = RtcpSender::default();
+    let timeout = timeout.as_micros() as u64;
+    let mut result = Vec::new();
+    for conn in conn_list.iter() {
+        let streams = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in streams.iter() {
+            let (ssrc, first, last) = match (ssrc, pkts.first(), pkts.last()) {
+                (Some(ssrc), Some(first), Some(last)) => (*ssrc, first.ts, last.ts),
+                _ => continue,
+            };
+            let sender = senders.get(&(conn.header.remote, ssrc, *dir)).unwrap_or(&empty);
+
+            let intervals: Vec<u64> = sender
+                .pkts
+                .windows(2)
+                .map(|w| w[1].0.duration_since(w[0].0).unwrap_or_default().as_micros() as u64)
+                .collect();
+            let rtp_bytes = pkts
+                .iter()
+                .filter_map(|p| p.rtp())
+                .map(|rtp| rtp.len as u64 + UDP_IP_OVERHEAD)
+                .sum::<u64>();
+            let rtcp_bytes = sender.pkts.iter().map(|p| p.1 as u64 + UDP_IP_OVERHEAD).sum::<u64>();
+            let rtcp_share = if rtp_bytes + rtcp_bytes > 0 {
+                rtcp_bytes as f64 / (rtp_bytes + rtcp_bytes) as f64 * 100.0
+            } else {
+                0.0
+            };
+
+            // silence is measured from the first media packet to the last
+            let mut marks = vec![first];
+            marks.extend(sender.pkts.iter().map(|p| p.0).filter(|ts| *ts > first && *ts < last));
+            marks.push(last);
+            let silent_periods = marks
+                .windows(2)
+                .map(|w| (w[0], w[1].duration_since(w[0]).unwrap_or_default().as_micros() as u64))
+                .filter(|(_, len)| *len > timeout)
+                .collect();
+
+            result.push(RtcpConformance {
+                ssrc,
+                dir: *dir,
+                remote: conn.header.remote,
+                rtcp_pkts: sender.pkts.len(),
+                interval_min: intervals.iter().min().copied(),
+                interval_avg: match intervals.len() {
+                    0 => None,
+                    n => Some(intervals.iter().sum::<u64>() / n as u64),
+                },
+                interval_max: intervals.iter().max().copied(),
+                rtp_bytes,
+                rtcp_bytes,
+                rtcp_share,
+                missing_sr: !sender.sr,
+                missing_rr: !reported.contains(&ssrc),
+                silent_periods,
+            });
+        }
+    }
+    result.sort_by_key(|c| (c.remote, c.dir == FlowDirection::Egress));
+    result
+}
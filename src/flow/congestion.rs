@@ -0,0 +1,153 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use super::{FlowPacket, call::timestamp_delta, timestamp::MediaClock};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EpisodeKind {
+    /// queue building up, delay increasing
+    BuildUp,
+    /// queue draining, delay decreasing
+    Release,
+}
+pub struct CongestionEpisode {
+    pub kind: EpisodeKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    /// queuing delay change over the episode, usec
+    pub magnitude: u64,
+}
+// trendline estimator parameters, as used by GCC
+const WINDOW_SIZE: usize = 20;
+const SMOOTHING: f64 = 0.9;
+const THRESHOLD_GAIN: f64 = 4.0;
+const MAX_DELTAS: f64 = 60.0;
+// adaptive threshold parameters, msec
+const K_UP: f64 = 0.0087;
+const K_DOWN: f64 = 0.039;
+const INITIAL_THRESHOLD: f64 = 12.5;
+const MIN_THRESHOLD: f64 = 6.0;
+const MAX_THRESHOLD: f64 = 600.0;
+/// overuse must be sustained this long before an episode starts, msec
+const OVERUSE_TIME: f64 = 10.0;
+
+struct Group {
+    ts: u32,
+    media: i64,
+    arrival: SystemTime,
+}
+/// Detect queue build-up and release episodes from the delay gradient of
+/// frame groups, a packet group is all packets sharing an rtp timestamp.
+pub fn detect_episodes(rx_pkts: &[FlowPacket], clock_rate: u32) -> Vec<CongestionEpisode> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut clock = MediaClock::new(clock_rate);
+    for p in rx_pkts.iter() {
+        let (rtp, media) = match (p.rtp(), clock.update(p)) {
+            (Some(rtp), Some((_, media))) => (rtp, media),
+            _ => continue,
+        };
+        match groups.last_mut() {
+            Some(g) if g.ts == rtp.header.timestamp => g.arrival = p.ts,
+            Some(g) if timestamp_delta(rtp.header.timestamp, g.ts) < 0 => continue, // reordered
+            _ => groups.push(Group {
+                ts: rtp.header.timestamp,
+                media,
+                arrival: p.ts,
+            }),
+        }
+    }
+    if groups.is_empty() {
+        return vec![];
+    }
+
+    let first_arrival = groups[0].arrival;
+    let mut episodes = Vec::new();
+    let mut window = VecDeque::with_capacity(WINDOW_SIZE);
+    let mut num_deltas = 0usize;
+    let mut accumulated = 0.0;
+    let mut smoothed = 0.0;
+    let mut threshold = INITIAL_THRESHOLD;
+    let mut last_trend = 0.0;
+    let mut overuse_time = 0.0;
+    let mut state: Option<(EpisodeKind, SystemTime, f64)> = None;
+    for pair in groups.windows(2) {
+        let (g1, g2) = (&pair[0], &pair[1]);
+        let arrival_delta = match g2.arrival.duration_since(g1.arrival) {
+            Ok(d) => d.as_micros() as f64 / 1000.0,
+            Err(_) => continue,
+        };
+        let send_delta = (g2.media - g1.media) as f64 / 1000.0;
+        num_deltas += 1;
+        accumulated += arrival_delta - send_delta;
+        smoothed = SMOOTHING * smoothed + (1.0 - SMOOTHING) * accumulated;
+        let now = g2.arrival.duration_since(first_arrival).unwrap().as_micros() as f64 / 1000.0;
+        if window.len() == WINDOW_SIZE {
+            window.pop_front();
+        }
+        window.push_back((now, smoothed));
+        if window.len() < WINDOW_SIZE {
+            continue;
+        }
+        let trend = linear_fit_slope(&window) * (num_deltas as f64).min(MAX_DELTAS) * THRESHOLD_GAIN;
+
+        let detected = if trend > threshold {
+            overuse_time += arrival_delta;
+            if overuse_time > OVERUSE_TIME && trend >= last_trend {
+                Some(EpisodeKind::BuildUp)
+            } else {
+                state.map(|(kind, _, _)| kind).filter(|kind| *kind == EpisodeKind::BuildUp)
+            }
+        } else if trend < -threshold {
+            overuse_time = 0.0;
+            Some(EpisodeKind::Release)
+        } else {
+            overuse_time = 0.0;
+            None
+        };
+        match (state, detected) {
+            (Some((kind, _, _)), Some(d)) if kind == d => (),
+            (Some((kind, start, start_delay)), _) => {
+                episodes.push(CongestionEpisode {
+                    kind,
+                    start,
+                    end: g2.arrival,
+                    magnitude: ((smoothed - start_delay).abs() * 1000.0) as u64,
+                });
+                state = detected.map(|d| (d, g2.arrival, smoothed));
+            }
+            (None, Some(d)) => state = Some((d, g2.arrival, smoothed)),
+            (None, None) => (),
+        }
+
+        if trend.abs() <= threshold + 15.0 {
+            let k = if trend.abs() < threshold { K_DOWN } else { K_UP };
+            threshold += k * (trend.abs() - threshold) * arrival_delta.min(100.0);
+            threshold = threshold.clamp(MIN_THRESHOLD, MAX_THRESHOLD);
+        }
+        last_trend = trend;
+    }
+    if let Some((kind, start, start_delay)) = state {
+        episodes.push(CongestionEpisode {
+            kind,
+            start,
+            end: groups.last().unwrap().arrival,
+            magnitude: ((smoothed - start_delay).abs() * 1000.0) as u64,
+        });
+    }
+    episodes
+}
+fn linear_fit_slope(points: &VecDeque<(f64, f64)>) -> f64 {
+    let n = points.len() as f64;
+    let x_avg = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let y_avg = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let mut numerator = 0.0;
+    let mut denominator = 0.0;
+    for (x, y) in points.iter() {
+        numerator += (x - x_avg) * (y - y_avg);
+        denominator += (x - x_avg) * (x - x_avg);
+    }
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
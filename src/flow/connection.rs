@@ -1,6 +1,12 @@
 use log;
 use crate::{flow::FlowDirection, structs::rtp::{RTP, RtpHeader}};
-use super::{FlowData, FlowHeader, FlowPacket, FlowPayload, FlowType};
+use super::{
+    FlowData, FlowHeader, FlowPacket, FlowPayload, FlowType,
+    call::DEFAULT_CLOCK_RATE,
+    congestion::{self, CongestionEpisode},
+    lipsync,
+    sdp::SdpInfo,
+};
 use std::{collections::HashMap, net::SocketAddr, time::{Duration, SystemTime}};
 
 #[derive(Clone)]
@@ -14,6 +20,13 @@ pub struct Connection {
     pub header: ConnectionHeader,
     pub ingress_pkts: Vec<FlowPacket>,
     pub egress_pkts: Vec<FlowPacket>,
+    pub ingress_rtcp: Vec<FlowPacket>,
+    pub egress_rtcp: Vec<FlowPacket>,
+    /// BUNDLE media id, from the first packet carrying the MID extension
+    pub mid: Option<String>,
+    /// rtp clock rate of each direction, resolved once the capture is read
+    pub ingress_clock_rate: u32,
+    pub egress_clock_rate: u32,
 }
 
 impl Connection {
@@ -52,6 +65,9 @@ impl Connection {
         }
         None
     }
+    pub fn ingress_congestion(&self) -> Vec<CongestionEpisode> {
+        congestion::detect_episodes(&self.ingress_pkts, self.ingress_clock_rate)
+    }
 }
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct ConnectionKey {
@@ -60,42 +76,139 @@ pub struct ConnectionKey {
 }
 pub struct ConnectionMap {
     pub map: HashMap<ConnectionKey, Vec<Connection>>,
+    /// MID extension id, keeps bundled streams of different media apart
+    pub mid_id: Option<u8>,
+    /// rtcp seen before any rtp of its connection, by rtcp remote address
+    pending_rtcp: HashMap<SocketAddr, Vec<(FlowDirection, u32, FlowPacket)>>,
 }
 
 impl ConnectionMap {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            mid_id: None,
+            pending_rtcp: HashMap::new(),
+        }
+    }
+    /// clock rate of every stream, from the SDP or static payload type, else
+    /// from the stream's sender reports
+    pub fn resolve_clock_rates(&mut self, sdp: &SdpInfo) {
+        for conn in self.map.values_mut().flatten() {
+            conn.ingress_clock_rate = stream_clock_rate(&conn.ingress_pkts, &conn.ingress_rtcp, sdp);
+            conn.egress_clock_rate = stream_clock_rate(&conn.egress_pkts, &conn.egress_rtcp, sdp);
         }
     }
     pub fn add(&mut self, d: FlowData) {
         if let FlowPayload::Rtp(ref rtp) = d.payload {
+            let remote = d.header.remote;
             let key = ConnectionKey {
-                remote: d.header.remote,
+                remote,
                 // ssrc: rtp.header.ssrc,
             };
+            let mid = mid(rtp, self.mid_id);
             match self.map.get_mut(&key) {
                 Some(conn_list) => {
-                    if let Some(conn) = find_conn(d.ts, d.header.dir, &rtp, conn_list) {
-                        conn_add_flow_data(conn, d);
+                    if let Some(conn) = find_conn(d.ts, d.header.dir, rtp, mid.as_deref(), conn_list) {
+                        conn_add_flow_data(conn, d, mid);
                     } else {
                         let mut conn = make_conn(&d.header);
-                        conn_add_flow_data(&mut conn, d);
+                        conn_add_flow_data(&mut conn, d, mid);
                         conn_list.push(conn);
                     }
                 },
                 None => {
                     let mut conn = make_conn(&d.header);
-                    conn_add_flow_data(&mut conn, d);
+                    conn_add_flow_data(&mut conn, d, mid);
                     self.map.insert(key, vec![conn]);
+                    self.flush_rtcp(remote);
                 },
             }
+        } else if let FlowPayload::Rtcp(ref rtcp) = d.payload {
+            let (dir, ssrc, remote) = (d.header.dir, rtcp.header.ssrc, d.header.remote);
+            // multiplexed on the rtp port when a stream there has the sender's
+            // ssrc, on rtp port + 1 otherwise
+            let muxed = ConnectionKey { remote };
+            let below = ConnectionKey {
+                remote: SocketAddr::new(remote.ip(), remote.port().wrapping_sub(1)),
+            };
+            let owned = self
+                .map
+                .get(&muxed)
+                .is_some_and(|list| list.iter().any(|c| sends(c, dir, ssrc)));
+            let key = if owned || !self.map.contains_key(&below) { muxed } else { below };
+            let pkt = FlowPacket {
+                ts: d.ts,
+                payload: d.payload,
+            };
+            match self.map.get_mut(&key) {
+                Some(conn_list) => add_rtcp(find_rtcp_conn(dir, ssrc, conn_list), dir, pkt),
+                None => self.pending_rtcp.entry(remote).or_default().push((dir, ssrc, pkt)),
+            }
+        }
+    }
+    /// hand rtcp that came early to the first connection on `remote`
+    fn flush_rtcp(&mut self, remote: SocketAddr) {
+        let conn_list = match self.map.get_mut(&ConnectionKey { remote }) {
+            Some(conn_list) => conn_list,
+            None => return,
+        };
+        let addrs = [remote, SocketAddr::new(remote.ip(), remote.port().wrapping_add(1))];
+        for addr in addrs.iter() {
+            for (dir, ssrc, pkt) in self.pending_rtcp.remove(addr).unwrap_or_default() {
+                add_rtcp(find_rtcp_conn(dir, ssrc, conn_list), dir, pkt);
+            }
         }
     }
 }
+fn sends(c: &Connection, dir: FlowDirection, ssrc: u32) -> bool {
+    if dir == FlowDirection::Ingress {
+        c.ingress_ssrc() == Some(ssrc)
+    } else {
+        c.egress_ssrc() == Some(ssrc)
+    }
+}
+/// the connection sending media with the rtcp sender's ssrc, or the latest one
+fn find_rtcp_conn(dir: FlowDirection, ssrc: u32, list: &mut [Connection]) -> &mut Connection {
+    let idx = list.iter().rposition(|c| sends(c, dir, ssrc));
+    let idx = idx.unwrap_or(list.len() - 1);
+    &mut list[idx]
+}
+fn add_rtcp(conn: &mut Connection, dir: FlowDirection, pkt: FlowPacket) {
+    if dir == FlowDirection::Ingress {
+        conn.ingress_rtcp.push(pkt);
+    } else {
+        conn.egress_rtcp.push(pkt);
+    }
+}
+/// `sender_rtcp` is the rtcp flowing the same way as `pkts`
+fn stream_clock_rate(pkts: &[FlowPacket], sender_rtcp: &[FlowPacket], sdp: &SdpInfo) -> u32 {
+    let rtp = match pkts.first().and_then(|p| p.rtp()) {
+        Some(rtp) => rtp,
+        None => return DEFAULT_CLOCK_RATE,
+    };
+    sdp.clock_rate(rtp.header.payload)
+        .or_else(|| lipsync::sender_clock_rate(sender_rtcp, rtp.header.ssrc))
+        .unwrap_or(DEFAULT_CLOCK_RATE)
+}
+pub fn mid(rtp: &RTP, mid_id: Option<u8>) -> Option<String> {
+    let data = rtp.header.extension(mid_id?)?;
+    Some(String::from_utf8_lossy(data).into_owned())
+}
 const THREHOLD_SECS: u64 = 1;
-fn find_conn<'a>(ts: SystemTime, dir: FlowDirection, rtp: &RTP, list: &'a mut Vec<Connection>) -> Option<&'a mut Connection> {
+fn find_conn<'a>(
+    ts: SystemTime,
+    dir: FlowDirection,
+    rtp: &RTP,
+    mid: Option<&str>,
+    list: &'a mut [Connection],
+) -> Option<&'a mut Connection> {
     for c in list.iter_mut().rev() {
+        // bundled streams of another media are not the reverse direction
+        if let (Some(a), Some(b)) = (c.mid.as_deref(), mid) {
+            if a != b {
+                continue;
+            }
+        }
         if dir == FlowDirection::Ingress {
             if let Some(ssrc) = c.ingress_ssrc() {
                 if ssrc == rtp.header.ssrc {
@@ -134,9 +247,17 @@ fn make_conn(header: &FlowHeader) -> Connection {
         header,
         ingress_pkts: vec![],
         egress_pkts: vec![],
+        ingress_rtcp: vec![],
+        egress_rtcp: vec![],
+        mid: None,
+        ingress_clock_rate: DEFAULT_CLOCK_RATE,
+        egress_clock_rate: DEFAULT_CLOCK_RATE,
     }
 }
-fn conn_add_flow_data(conn: &mut Connection, d: FlowData) {
+fn conn_add_flow_data(conn: &mut Connection, d: FlowData, mid: Option<String>) {
+    if conn.mid.is_none() {
+        conn.mid = mid;
+    }
     if d.header.dir == FlowDirection::Ingress {
         conn.ingress_pkts.push(FlowPacket {
             ts: d.ts,
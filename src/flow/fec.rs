@@ -0,0 +1,404 @@
+use std::{collections::{HashMap, HashSet}, net::SocketAddr, time::SystemTime};
+
+use super::{FlowDirection, FlowPacket, call::{seq_delta, timestamp_delta}, connection::Connection, sdp::SdpInfo};
+
+const FLEXFEC_NAMES: [&str; 2] = ["flexfec-03", "flexfec"];
+/// (capture ts, sn base, offsets) of the flexfec packets protecting a stream
+type FlexFecMasks = Vec<(SystemTime, u16, Vec<u16>)>;
+
+/// RED and FEC protection of one media stream, measured at the capture point
+pub struct FecStats {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub red: bool,
+    pub ulpfec: bool,
+    pub flexfec_ssrc: Option<u32>,
+    pub media_pkts: usize,
+    pub fec_pkts: usize,
+    /// seqs missing before any repair
+    pub pre_fec_lost: usize,
+    pub pre_fec_lost_rate: f64,
+    /// repairable from RED redundant blocks
+    pub recovered_by_red: usize,
+    /// repairable by xor of received packets and FEC
+    pub recovered_by_fec: usize,
+    pub post_fec_lost: usize,
+    pub post_fec_lost_rate: f64,
+    pub media_bytes: u64,
+    /// RED redundant blocks and FEC packets
+    pub redundancy_bytes: u64,
+}
+
+impl FecStats {
+    /// redundancy sent per media byte, percent
+    pub fn overhead(&self) -> f64 {
+        if self.media_bytes == 0 {
+            return 0.0;
+        }
+        self.redundancy_bytes as f64 / self.media_bytes as f64 * 100.0
+    }
+}
+
+struct Stream<'a> {
+    ssrc: u32,
+    dir: FlowDirection,
+    remote: SocketAddr,
+    pkts: &'a [FlowPacket],
+}
+
+/// Find the RED, ULPFEC and FlexFEC protected streams, with payload types
+/// from SDP or the command line, and work out which lost packets the
+/// redundancy could have repaired.
+pub fn analyze(conns: &[&Connection], sdp: &SdpInfo) -> Vec<FecStats> {
+    let red_pts: HashSet<u8> = sdp.payload_types("red").into_iter().collect();
+    let ulpfec_pts: HashSet<u8> = sdp.payload_types("ulpfec").into_iter().collect();
+    let flexfec_pts: HashSet<u8> = FLEXFEC_NAMES.iter().flat_map(|n| sdp.payload_types(n)).collect();
+
+    let mut streams = Vec::new();
+    let mut flexfec_streams = Vec::new();
+    for conn in conns.iter() {
+        let list = [(FlowDirection::Ingress, &conn.ingress_pkts), (FlowDirection::Egress, &conn.egress_pkts)];
+        for (dir, pkts) in list.iter() {
+            if let Some(rtp) = pkts.first().and_then(|p| p.rtp()) {
+                let stream = Stream {
+                    ssrc: rtp.header.ssrc,
+                    dir: *dir,
+                    remote: conn.header.remote,
+                    pkts,
+                };
+                if flexfec_pts.contains(&rtp.header.payload) {
+                    flexfec_streams.push(stream);
+                } else {
+                    streams.push(stream);
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for s in streams.iter() {
+        // flexfec protecting this stream, sent alongside it
+        let mut flexfec = None;
+        for f in flexfec_streams
+            .iter()
+            .filter(|f| f.dir == s.dir && f.remote.ip() == s.remote.ip())
+        {
+            let mut protected: FlexFecMasks = Vec::new();
+            for p in f.pkts.iter() {
+                let masks = match p.rtp().and_then(|rtp| parse_flexfec(&rtp.payload)) {
+                    Some(masks) => masks,
+                    None => continue,
+                };
+                for (ssrc, base, offsets) in masks.into_iter() {
+                    if ssrc == s.ssrc {
+                        protected.push((p.ts, base, offsets));
+                    }
+                }
+            }
+            if !protected.is_empty() {
+                flexfec = Some((f, protected));
+                break;
+            }
+        }
+        if let Some(stats) = measure(s, flexfec, &red_pts, &ulpfec_pts) {
+            result.push(stats);
+        }
+    }
+    result
+}
+
+fn measure(
+    stream: &Stream,
+    flexfec: Option<(&Stream, FlexFecMasks)>,
+    red_pts: &HashSet<u8>,
+    ulpfec_pts: &HashSet<u8>,
+) -> Option<FecStats> {
+    let mut timeline: Vec<(SystemTime, i64)> = Vec::new();
+    let mut fec_sets: Vec<Vec<i64>> = Vec::new();
+    // (seq, timestamp offset) of every redundant RED block
+    let mut red_blocks: Vec<(i64, u16)> = Vec::new();
+    // (seq, rtp timestamp) of media packets, for the frame duration
+    let mut media: Vec<(i64, u32)> = Vec::new();
+    let (mut red, mut ulpfec) = (false, false);
+    let (mut fec_pkts, mut media_bytes, mut redundancy_bytes) = (0, 0u64, 0u64);
+    for p in stream.pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        let seq = match timeline.last() {
+            Some((_, last)) => last + seq_delta(rtp.header.seqnum, *last as u16),
+            None => rtp.header.seqnum as i64,
+        };
+        timeline.push((p.ts, seq));
+
+        let mut primary = (rtp.header.payload, &rtp.payload[..]);
+        if red_pts.contains(&rtp.header.payload) {
+            let blocks = match parse_red(&rtp.payload) {
+                Some(blocks) => blocks,
+                None => continue,
+            };
+            red = true;
+            let (last, redundant) = blocks.split_last().unwrap();
+            for (_, offset, data) in redundant.iter() {
+                red_blocks.push((seq, *offset));
+                redundancy_bytes += data.len() as u64;
+            }
+            primary = (last.0, last.2);
+        }
+        if ulpfec_pts.contains(&primary.0) {
+            ulpfec = true;
+            fec_pkts += 1;
+            redundancy_bytes += primary.1.len() as u64;
+            if let Some((base, offsets)) = parse_ulpfec(primary.1) {
+                let base = seq + seq_delta(base, rtp.header.seqnum);
+                fec_sets.push(offsets.iter().map(|o| base + *o as i64).collect());
+            }
+        } else {
+            media.push((seq, rtp.header.timestamp));
+            media_bytes += primary.1.len() as u64;
+        }
+    }
+    let flexfec_ssrc = flexfec.as_ref().map(|(f, _)| f.ssrc);
+    if let Some((f, protected)) = flexfec {
+        fec_pkts += protected.len();
+        redundancy_bytes += f.pkts.iter().filter_map(|p| p.rtp()).map(|rtp| rtp.payload.len() as u64).sum::<u64>();
+        for (ts, base, offsets) in protected.into_iter() {
+            // sn base is extended around the stream's latest seq at arrival
+            let idx = timeline.partition_point(|t| t.0 <= ts).max(1) - 1;
+            let reference = timeline.get(idx)?.1;
+            let base = reference + seq_delta(base, reference as u16);
+            fec_sets.push(offsets.iter().map(|o| base + *o as i64).collect());
+        }
+    }
+    if !red && !ulpfec && flexfec_ssrc.is_none() {
+        return None;
+    }
+
+    let received: HashSet<i64> = timeline.iter().map(|t| t.1).collect();
+    let first = *received.iter().min()?;
+    let last = *received.iter().max()?;
+    let expected = (last - first + 1) as usize;
+    let missing: HashSet<i64> = (first..=last).filter(|seq| !received.contains(seq)).collect();
+
+    // a redundant block repeats the packet one frame duration per offset step back
+    let frame = frame_duration(&media);
+    let mut recovered: HashSet<i64> = HashSet::new();
+    if let Some(frame) = frame {
+        for (seq, offset) in red_blocks.iter() {
+            let distance = (*offset as i64 + frame / 2) / frame;
+            if distance > 0 && missing.contains(&(seq - distance)) {
+                recovered.insert(seq - distance);
+            }
+        }
+    }
+    let recovered_by_red = recovered.len();
+    // xor recovers the single missing packet of a set, which may complete others
+    let mut progress = true;
+    while progress {
+        progress = false;
+        for set in fec_sets.iter() {
+            let lost: Vec<i64> = set
+                .iter()
+                .copied()
+                .filter(|seq| !received.contains(seq) && !recovered.contains(seq))
+                .collect();
+            if lost.len() == 1 && missing.contains(&lost[0]) {
+                recovered.insert(lost[0]);
+                progress = true;
+            }
+        }
+    }
+    let recovered_by_fec = recovered.len() - recovered_by_red;
+    let post_fec_lost = missing.len() - recovered.len();
+    let rate = |lost: usize| lost as f64 / expected as f64 * 100.0;
+    Some(FecStats {
+        ssrc: stream.ssrc,
+        dir: stream.dir,
+        remote: stream.remote,
+        red,
+        ulpfec,
+        flexfec_ssrc,
+        media_pkts: media.len(),
+        fec_pkts,
+        pre_fec_lost: missing.len(),
+        pre_fec_lost_rate: rate(missing.len()),
+        recovered_by_red,
+        recovered_by_fec,
+        post_fec_lost,
+        post_fec_lost_rate: rate(post_fec_lost),
+        media_bytes,
+        redundancy_bytes,
+    })
+}
+
+/// most common rtp timestamp step between consecutive media packets
+fn frame_duration(media: &[(i64, u32)]) -> Option<i64> {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for w in media.windows(2) {
+        let step = timestamp_delta(w[1].1, w[0].1);
+        if w[1].0 - w[0].0 == 1 && step > 0 {
+            *counts.entry(step).or_default() += 1;
+        }
+    }
+    counts.into_iter().max_by_key(|(_, n)| *n).map(|(step, _)| step)
+}
+
+/// RFC 2198 blocks as (pt, timestamp offset, data), the primary block last
+fn parse_red(payload: &[u8]) -> Option<Vec<(u8, u16, &[u8])>> {
+    let mut headers = Vec::new();
+    let mut pos = 0;
+    loop {
+        let b = *payload.get(pos)?;
+        if b & 0x80 == 0 {
+            headers.push((b & 0x7f, 0, None));
+            pos += 1;
+            break;
+        }
+        let h = payload.get(pos..pos + 4)?;
+        let offset = (h[1] as u16) << 6 | (h[2] as u16) >> 2;
+        let len = ((h[2] & 0x03) as usize) << 8 | h[3] as usize;
+        headers.push((b & 0x7f, offset, Some(len)));
+        pos += 4;
+    }
+    let mut blocks = Vec::with_capacity(headers.len());
+    for (pt, offset, len) in headers.into_iter() {
+        let len = len.unwrap_or(payload.len().saturating_sub(pos));
+        blocks.push((pt, offset, payload.get(pos..pos + len)?));
+        pos += len;
+    }
+    Some(blocks)
+}
+
+/// RFC 5109 sn base and protected seq offsets of the level 0 mask
+fn parse_ulpfec(data: &[u8]) -> Option<(u16, Vec<u16>)> {
+    let long_mask = data.first()? & 0x40 != 0;
+    let sn_base = (*data.get(2)? as u16) << 8 | *data.get(3)? as u16;
+    // 10 byte fec header, then protection length and mask of level 0
+    let mask = data.get(12..(if long_mask { 18 } else { 14 }))?;
+    let mut offsets = Vec::new();
+    for (i, byte) in mask.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                offsets.push((i * 8 + bit) as u16);
+            }
+        }
+    }
+    Some((sn_base, offsets))
+}
+
+/// flexfec-03 flexible mask packets as (protected ssrc, sn base, offsets),
+/// retransmission and fixed L/D masks are not decoded
+fn parse_flexfec(data: &[u8]) -> Option<Vec<(u32, u16, Vec<u16>)>> {
+    let b = *data.first()?;
+    if b & 0xc0 != 0 {
+        return None;
+    }
+    let be16 = |pos: usize| Some((*data.get(pos)? as u64) << 8 | *data.get(pos + 1)? as u64);
+    let count = *data.get(8)?;
+    let mut pos = 12;
+    let mut result = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let ssrc = (be16(pos)? << 16 | be16(pos + 2)?) as u32;
+        let sn_base = be16(pos + 4)? as u16;
+        pos += 6;
+        // each mask chunk starts with a k bit set on the last one
+        let mut offsets = Vec::new();
+        let mut first_offset = 0;
+        for words in [1, 2, 4].iter() {
+            let mut chunk = 0u64;
+            for i in 0..*words {
+                chunk = chunk << 16 | be16(pos + i * 2)?;
+            }
+            pos += words * 2;
+            let bits = words * 16 - 1;
+            for i in 0..bits {
+                if chunk & (1 << (bits - 1 - i)) != 0 {
+                    offsets.push((first_offset + i) as u16);
+                }
+            }
+            first_offset += bits;
+            if chunk & (1 << bits) != 0 {
+                break;
+            }
+        }
+        result.push((ssrc, sn_base, offsets));
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 5109 fec header and level 0 header with the given mask
+    fn ulpfec(long_mask: bool, sn_base: u16, mask: &[u8]) -> Vec<u8> {
+        let mut data = vec![if long_mask { 0x40 } else { 0x00 }, 0x60];
+        data.extend_from_slice(&sn_base.to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        data.extend_from_slice(&[0x00, 0x20]);
+        data.extend_from_slice(mask);
+        data.extend_from_slice(&[0xaa; 20]);
+        data
+    }
+
+    /// flexfec-03 header protecting one ssrc with the given mask chunks
+    fn flexfec(ssrc: u32, sn_base: u16, mask: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x00, 0x60, 0x00, 0x20, 0, 0, 0, 0, 1, 0, 0, 0];
+        data.extend_from_slice(&ssrc.to_be_bytes());
+        data.extend_from_slice(&sn_base.to_be_bytes());
+        data.extend_from_slice(mask);
+        data.extend_from_slice(&[0xaa; 20]);
+        data
+    }
+
+    #[test]
+    fn ulpfec_short_mask() {
+        let data = ulpfec(false, 1000, &[0xa0, 0x01]);
+        assert_eq!(parse_ulpfec(&data), Some((1000, vec![0, 2, 15])));
+    }
+
+    #[test]
+    fn ulpfec_long_mask() {
+        // L bit: 48 bit mask
+        let data = ulpfec(true, 65535, &[0x80, 0x00, 0x00, 0x00, 0x10, 0x01]);
+        assert_eq!(parse_ulpfec(&data), Some((65535, vec![0, 35, 47])));
+    }
+
+    #[test]
+    fn ulpfec_truncated_long_mask() {
+        let mut data = ulpfec(true, 1, &[0xff; 6]);
+        data.truncate(16);
+        assert_eq!(parse_ulpfec(&data), None);
+    }
+
+    #[test]
+    fn flexfec_one_word_mask() {
+        // k set, offsets 0 and 14
+        let data = flexfec(0x11223344, 500, &[0xc0, 0x01]);
+        assert_eq!(parse_flexfec(&data), Some(vec![(0x11223344, 500, vec![0, 14])]));
+    }
+
+    #[test]
+    fn flexfec_two_word_mask() {
+        // offset 14 in the first chunk, 15 and 45 in the second
+        let data = flexfec(1, 500, &[0x00, 0x01, 0xc0, 0x00, 0x00, 0x01]);
+        assert_eq!(parse_flexfec(&data), Some(vec![(1, 500, vec![14, 15, 45])]));
+    }
+
+    #[test]
+    fn flexfec_four_word_mask() {
+        // only the last 63 bit chunk has offsets, 46 and 108
+        let mask = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0, 0, 0, 0, 0, 0, 0x01];
+        let data = flexfec(1, 7, &mask);
+        assert_eq!(parse_flexfec(&data), Some(vec![(1, 7, vec![46, 108])]));
+    }
+
+    #[test]
+    fn flexfec_retransmission_not_decoded() {
+        let mut data = flexfec(1, 7, &[0xc0, 0x01]);
+        data[0] |= 0x40;
+        assert_eq!(parse_flexfec(&data), None);
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::SystemTime;
+
+use crate::structs::rtcp::{PayloadFeedback, RtcpPacket, TransportFeedback};
+
+use super::FlowPacket;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeyframeRequest {
+    Pli,
+    Fir,
+}
+#[derive(Default)]
+pub struct FeedbackStats {
+    pub nack_pkts: u64,
+    /// retransmission requested packets, counted once per request
+    pub nack_seqs: u64,
+    pub pli_pkts: u64,
+    pub fir_pkts: u64,
+    pub remb_pkts: u64,
+    pub twcc_pkts: u64,
+    pub keyframe_requests: Vec<(SystemTime, KeyframeRequest)>,
+    pub retransmission_requests: Vec<(SystemTime, Vec<u16>)>,
+}
+
+/// Count RTCP feedback messages and build keyframe and retransmission request timelines.
+pub fn collect(rtcp_pkts: &[FlowPacket]) -> FeedbackStats {
+    let mut stats = FeedbackStats::default();
+    for p in rtcp_pkts.iter() {
+        let rtcp = match p.rtcp() {
+            Some(rtcp) => rtcp,
+            None => continue,
+        };
+        for packet in rtcp.packets.iter() {
+            match packet {
+                RtcpPacket::TransportFeedback(TransportFeedback::Nack(nack)) => {
+                    stats.nack_pkts += 1;
+                    stats.nack_seqs += nack.lost.len() as u64;
+                    stats.retransmission_requests.push((p.ts, nack.lost.clone()));
+                }
+                RtcpPacket::TransportFeedback(TransportFeedback::TransportCc(_)) => {
+                    stats.twcc_pkts += 1;
+                }
+                RtcpPacket::PayloadFeedback(PayloadFeedback::Pli { .. }) => {
+                    stats.pli_pkts += 1;
+                    stats.keyframe_requests.push((p.ts, KeyframeRequest::Pli));
+                }
+                RtcpPacket::PayloadFeedback(PayloadFeedback::Fir { .. }) => {
+                    stats.fir_pkts += 1;
+                    stats.keyframe_requests.push((p.ts, KeyframeRequest::Fir));
+                }
+                RtcpPacket::PayloadFeedback(PayloadFeedback::Remb(_)) => {
+                    stats.remb_pkts += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+    stats
+}
@@ -0,0 +1,216 @@
+use crate::errors::*;
+use std::{collections::{HashSet, VecDeque}, str::FromStr};
+
+use super::{FlowPacket, call::seq_delta, timestamp::MediaClock};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JitterBufferMode {
+    Fixed,
+    Adaptive,
+}
+
+impl FromStr for JitterBufferMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fixed" => Ok(JitterBufferMode::Fixed),
+            "adaptive" => Ok(JitterBufferMode::Adaptive),
+            x => bail!("Unknown jitter buffer mode: {:?}", x),
+        }
+    }
+}
+pub struct JitterBufferConfig {
+    pub mode: JitterBufferMode,
+    /// buffer size of fixed mode, usec
+    pub size: u64,
+    /// adaptive mode delay bounds, usec
+    pub min_delay: u64,
+    pub max_delay: u64,
+}
+pub struct JitterBufferStats {
+    pub lost_pkts: u64,
+    pub late_pkts: u64,
+    pub effective_lost_rate: f64,
+    pub avg_delay: u64,
+    pub max_delay: u64,
+}
+/// number of recent packets the adaptive buffer sizes itself from
+const ADAPTIVE_WINDOW: usize = 100;
+
+/// Replay a received stream through a jitter buffer, packets arriving after
+/// their playout time are discarded as late.
+pub fn emulate(rx_pkts: &[FlowPacket], clock_rate: u32, config: &JitterBufferConfig) -> JitterBufferStats {
+    let mut clock = MediaClock::new(clock_rate);
+    let mut highest_seq = 0i64;
+    let mut lowest_seq = 0i64;
+    let mut highest_raw_seq = 0u16;
+    let mut seen = HashSet::with_capacity(rx_pkts.len());
+    let mut transits = VecDeque::with_capacity(ADAPTIVE_WINDOW);
+    // playout time = media time + playout offset
+    let mut playout_offset = 0i64;
+    let mut received_pkts = 0u64;
+    let mut late_pkts = 0u64;
+    let mut padding_pkts = 0u64;
+    let mut dtotal = 0u64;
+    let mut dmax = 0u64;
+
+    for p in rx_pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        // padding-only packets are never played out
+        let (arrival, media) = match clock.update(p) {
+            Some(t) => t,
+            None => {
+                padding_pkts += 1;
+                continue;
+            }
+        };
+        let seq = if received_pkts == 0 {
+            0
+        } else {
+            highest_seq + seq_delta(rtp.header.seqnum, highest_raw_seq)
+        };
+        if !seen.insert(seq) {
+            // duplicate
+            continue;
+        }
+        if received_pkts == 0 || seq > highest_seq {
+            highest_seq = seq;
+            highest_raw_seq = rtp.header.seqnum;
+        }
+        if seq < lowest_seq {
+            lowest_seq = seq;
+        }
+        let transit = arrival - media;
+        if transits.len() == ADAPTIVE_WINDOW {
+            transits.pop_front();
+        }
+        transits.push_back(transit);
+        let min_transit = *transits.iter().min().unwrap();
+
+        if received_pkts == 0 {
+            playout_offset = transit + match config.mode {
+                JitterBufferMode::Fixed => config.size as i64,
+                JitterBufferMode::Adaptive => config.min_delay as i64,
+            };
+        } else if config.mode == JitterBufferMode::Adaptive && rtp.header.marker != 0 {
+            // resize at talk-spurt start, where silence hides the change
+            playout_offset = min_transit + adaptive_target(&transits, min_transit, config);
+        }
+        received_pkts += 1;
+
+        if arrival > media + playout_offset {
+            late_pkts += 1;
+            if config.mode == JitterBufferMode::Adaptive {
+                playout_offset = playout_offset.max(min_transit + adaptive_target(&transits, min_transit, config));
+            }
+            continue;
+        }
+        let d = (playout_offset - min_transit).max(0) as u64;
+        dtotal += d;
+        if dmax < d {
+            dmax = d;
+        }
+    }
+
+    let expected_pkts = if received_pkts == 0 { 0 } else { (highest_seq - lowest_seq + 1) as u64 };
+    let lost_pkts = expected_pkts.saturating_sub(received_pkts + padding_pkts);
+    let played = received_pkts - late_pkts;
+    let effective_lost_rate = if expected_pkts == 0 {
+        0.0
+    } else {
+        (lost_pkts + late_pkts) as f64 / expected_pkts as f64 * 100.0
+    };
+    JitterBufferStats {
+        lost_pkts,
+        late_pkts,
+        effective_lost_rate,
+        avg_delay: dtotal.checked_div(played).unwrap_or(0),
+        max_delay: dmax,
+    }
+}
+/// delay needed to play the slowest recent packet in time
+fn adaptive_target(transits: &VecDeque<i64>, min_transit: i64, config: &JitterBufferConfig) -> i64 {
+    let peak = transits.iter().max().unwrap() - min_transit;
+    peak.max(config.min_delay as i64).min(config.max_delay as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    use crate::centrifuge::rtp;
+    use crate::flow::FlowPayload;
+
+    /// 8 kHz stream with 20 ms frames, packet `seq` delayed by `delays[seq]`
+    /// msec, in arrival order. `None` is a lost packet.
+    fn stream(delays: &[Option<u64>]) -> Vec<FlowPacket> {
+        let mut pkts: Vec<FlowPacket> = delays
+            .iter()
+            .enumerate()
+            .filter_map(|(seq, delay)| {
+                let mut data = vec![0x80, 0x00];
+                data.extend_from_slice(&(seq as u16).to_be_bytes());
+                data.extend_from_slice(&(seq as u32 * 160).to_be_bytes());
+                data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+                data.extend_from_slice(&[0x55; 12]);
+                Some(FlowPacket {
+                    ts: SystemTime::UNIX_EPOCH + Duration::from_millis(seq as u64 * 20 + (*delay)?),
+                    payload: FlowPayload::Rtp(rtp::extract(&data).unwrap()),
+                })
+            })
+            .collect();
+        pkts.sort_by_key(|p| p.ts);
+        pkts
+    }
+
+    fn config(mode: JitterBufferMode, size: u64, min_delay: u64, max_delay: u64) -> JitterBufferConfig {
+        JitterBufferConfig {
+            mode,
+            size: size * 1000,
+            min_delay: min_delay * 1000,
+            max_delay: max_delay * 1000,
+        }
+    }
+
+    #[test]
+    fn fixed_buffer_drops_late_packets() {
+        let pkts = stream(&[Some(0), Some(0), Some(0), Some(80), Some(0), Some(0), None, Some(0)]);
+        let stats = emulate(&pkts, 8000, &config(JitterBufferMode::Fixed, 60, 0, 0));
+        assert_eq!(stats.lost_pkts, 1);
+        assert_eq!(stats.late_pkts, 1);
+        assert_eq!(stats.effective_lost_rate, 25.0);
+        assert_eq!(stats.avg_delay, 60_000);
+        assert_eq!(stats.max_delay, 60_000);
+    }
+
+    #[test]
+    fn adaptive_buffer_grows_after_late_packet() {
+        let delays = [Some(0), Some(0), Some(50), Some(40), Some(0), Some(0)];
+        let pkts = stream(&delays);
+        let adaptive = emulate(&pkts, 8000, &config(JitterBufferMode::Adaptive, 0, 20, 200));
+        // the 50 ms packet is late, the buffer then holds the 40 ms one
+        assert_eq!(adaptive.late_pkts, 1);
+        assert_eq!(adaptive.avg_delay, 32_000);
+        assert_eq!(adaptive.max_delay, 50_000);
+        let fixed = emulate(&pkts, 8000, &config(JitterBufferMode::Fixed, 20, 0, 0));
+        assert_eq!(fixed.late_pkts, 2);
+        assert_eq!(fixed.max_delay, 20_000);
+    }
+
+    #[test]
+    fn adaptive_buffer_stays_below_max_delay() {
+        let mut delays = vec![Some(0); 20];
+        delays[1] = Some(300);
+        delays[10] = Some(250);
+        delays[16] = Some(150);
+        let stats = emulate(&stream(&delays), 8000, &config(JitterBufferMode::Adaptive, 0, 20, 200));
+        assert_eq!(stats.late_pkts, 2);
+        assert_eq!(stats.max_delay, 200_000);
+        assert_eq!(stats.lost_pkts, 0);
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, net::SocketAddr, time::{Duration, SystemTime}};
+
+use crate::structs::rtcp::RtcpPacket;
+
+use super::{FlowDirection, connection::Connection};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StreamEnd {
+    Bye,
+    Timeout,
+    CaptureEnd,
+}
+pub struct StreamLifecycle {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub first: SystemTime,
+    pub last: SystemTime,
+    pub sr_count: usize,
+    pub rr_count: usize,
+    /// average interval between SRs and between RRs of the ssrc, usec
+    pub sr_interval: Option<u64>,
+    pub rr_interval: Option<u64>,
+    pub bye: Option<(SystemTime, Option<String>)>,
+    pub end: StreamEnd,
+    pub pkts_after_bye: usize,
+}
+
+#[derive(Default)]
+struct RtcpHistory {
+    sr: Vec<SystemTime>,
+    rr: Vec<SystemTime>,
+    bye: Option<(SystemTime, Option<String>)>,
+}
+
+/// Follow every rtp stream from its first packet to how it ended: an RTCP
+/// BYE, no packets for `idle_timeout`, or the end of the capture.
+pub fn track(conn_list: &[Connection], idle_timeout: Duration) -> Vec<StreamLifecycle> {
+    // relayed rtcp shows up on both legs, each leg keeps its own history
+    let mut history: HashMap<(SocketAddr, u32, FlowDirection), RtcpHistory> = HashMap::new();
+    let mut capture_end = SystemTime::UNIX_EPOCH;
+    for conn in conn_list.iter() {
+        let all = conn.ingress_pkts.iter().chain(conn.egress_pkts.iter());
+        if let Some(last) = all.map(|p| p.ts).max() {
+            capture_end = capture_end.max(last);
+        }
+        let rtcp_pkts = [(FlowDirection::Ingress, &conn.ingress_rtcp), (FlowDirection::Egress, &conn.egress_rtcp)];
+        for (dir, pkts) in rtcp_pkts.iter() {
+            for p in pkts.iter() {
+                capture_end = capture_end.max(p.ts);
+                let rtcp = match p.rtcp() {
+                    Some(rtcp) => rtcp,
+                    None => continue,
+                };
+                let key = |ssrc: u32| (conn.header.remote, ssrc, *dir);
+                for packet in rtcp.packets.iter() {
+                    match packet {
+                        RtcpPacket::SenderReport(sr) => history.entry(key(sr.ssrc)).or_default().sr.push(p.ts),
+                        RtcpPacket::ReceiverReport(rr) => history.entry(key(rr.ssrc)).or_default().rr.push(p.ts),
+                        RtcpPacket::Goodbye(bye) => {
+                            for ssrc in bye.sources.iter() {
+                                let h = history.entry(key(*ssrc)).or_default();
+                                if h.bye.is_none() {
+                                    h.bye = Some((p.ts, bye.reason.clone()));
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    for h in history.values_mut() {
+        h.sr.sort();
+        h.rr.sort();
+    }
+
+    let empty = RtcpHistory::default();
+    let mut result = Vec::new();
+    for conn in conn_list.iter() {
+        let streams = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in streams.iter() {
+            let (ssrc, first, last) = match (ssrc, pkts.first(), pkts.last()) {
+                (Some(ssrc), Some(first), Some(last)) => (*ssrc, first.ts, last.ts),
+                _ => continue,
+            };
+            let h = history.get(&(conn.header.remote, ssrc, *dir)).unwrap_or(&empty);
+            let pkts_after_bye = match h.bye {
+                Some((bye_ts, _)) => pkts.iter().filter(|p| p.ts > bye_ts).count(),
+                None => 0,
+            };
+            let end = if h.bye.is_some() {
+                StreamEnd::Bye
+            } else if capture_end.duration_since(last).unwrap_or_default() > idle_timeout {
+                StreamEnd::Timeout
+            } else {
+                StreamEnd::CaptureEnd
+            };
+            result.push(StreamLifecycle {
+                ssrc,
+                dir: *dir,
+                remote: conn.header.remote,
+                first,
+                last,
+                sr_count: h.sr.len(),
+                rr_count: h.rr.len(),
+                sr_interval: average_interval(&h.sr),
+                rr_interval: average_interval(&h.rr),
+                bye: h.bye.clone(),
+                end,
+                pkts_after_bye,
+            });
+        }
+    }
+    result.sort_by_key(|s| s.first);
+    result
+}
+fn average_interval(ts: &[SystemTime]) -> Option<u64> {
+    if ts.len() < 2 {
+        return None;
+    }
+    let total = ts.last().unwrap().duration_since(ts[0]).unwrap_or_default();
+    Some(total.as_micros() as u64 / (ts.len() - 1) as u64)
+}
@@ -0,0 +1,228 @@
+use std::{collections::HashMap, net::IpAddr, time::SystemTime};
+
+use super::{FlowDirection, FlowPacket, call::timestamp_delta, connection::Connection, participant};
+
+/// ITU-R BT.1359 thresholds, usec, positive when audio leads video
+const DETECTABLE_LEAD: i64 = 45_000;
+const DETECTABLE_LAG: i64 = -125_000;
+const ACCEPTABLE_LEAD: i64 = 90_000;
+const ACCEPTABLE_LAG: i64 = -185_000;
+const VIDEO_CLOCK_RATE: u32 = 90_000;
+const CLOCK_RATES: [u32; 7] = [8_000, 16_000, 22_050, 32_000, 44_100, 48_000, 90_000];
+/// offset samples are averaged over this period, usec
+const SAMPLE_PERIOD: u128 = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum SyncLevel {
+    Undetectable,
+    Detectable,
+    Unacceptable,
+}
+pub struct LipSync {
+    pub cname: String,
+    pub remote: IpAddr,
+    pub dir: FlowDirection,
+    pub audio_ssrc: u32,
+    pub video_ssrc: u32,
+    /// (sample start, offset usec), positive when audio leads video
+    pub samples: Vec<(SystemTime, i64)>,
+    pub max_lead: i64,
+    pub max_lag: i64,
+    pub level: SyncLevel,
+}
+/// (capture ts, ntp usec, rtp timestamp)
+type SrMapping = (SystemTime, i64, u32);
+/// (cname, remote, dir) => (audio streams, video streams)
+type StreamGroups<'a> = HashMap<(String, IpAddr, FlowDirection), (Vec<Stream<'a>>, Vec<Stream<'a>>)>;
+/// (samples, max lead, max lag)
+type PairSync = (Vec<(SystemTime, i64)>, i64, i64);
+
+struct Stream<'a> {
+    ssrc: u32,
+    remote: IpAddr,
+    dir: FlowDirection,
+    pkts: &'a [FlowPacket],
+}
+
+/// Measure audio/video synchronization of streams sharing a CNAME, from the
+/// NTP/RTP timestamp mapping of their sender reports.
+pub fn measure(conn_list: &[Connection]) -> Vec<LipSync> {
+    let mut mappings: HashMap<u32, Vec<SrMapping>> = HashMap::new();
+    for conn in conn_list.iter() {
+        for p in conn.ingress_rtcp.iter().chain(conn.egress_rtcp.iter()) {
+            for (ssrc, mapping) in sr_mappings(p) {
+                mappings.entry(ssrc).or_default().push(mapping);
+            }
+        }
+    }
+    for m in mappings.values_mut() {
+        sort_mappings(m);
+    }
+    let cnames: HashMap<u32, String> = participant::collect_sdes(conn_list)
+        .into_iter()
+        .filter_map(|(ssrc, desc)| Some((ssrc, desc.cname?)))
+        .collect();
+    let clock_rates: HashMap<u32, u32> = mappings
+        .iter()
+        .filter_map(|(ssrc, m)| Some((*ssrc, estimate_clock_rate(m)?)))
+        .collect();
+
+    let mut groups: StreamGroups = HashMap::new();
+    for conn in conn_list.iter() {
+        let streams = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in streams.iter() {
+            let ssrc = match ssrc {
+                Some(ssrc) => *ssrc,
+                None => continue,
+            };
+            let (cname, rate) = match (cnames.get(&ssrc), clock_rates.get(&ssrc)) {
+                (Some(cname), Some(rate)) => (cname, *rate),
+                _ => continue,
+            };
+            let remote = conn.header.remote.ip();
+            let group = groups.entry((cname.clone(), remote, *dir)).or_default();
+            let stream = Stream {
+                ssrc,
+                remote,
+                dir: *dir,
+                pkts,
+            };
+            if rate == VIDEO_CLOCK_RATE {
+                group.1.push(stream);
+            } else {
+                group.0.push(stream);
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for ((cname, _, _), (audio, video)) in groups.iter() {
+        for a in audio.iter() {
+            for v in video.iter() {
+                let sync = measure_pair(a, v, &mappings, &clock_rates);
+                if let Some((samples, max_lead, max_lag)) = sync {
+                    let level = if max_lead > ACCEPTABLE_LEAD || max_lag < ACCEPTABLE_LAG {
+                        SyncLevel::Unacceptable
+                    } else if max_lead > DETECTABLE_LEAD || max_lag < DETECTABLE_LAG {
+                        SyncLevel::Detectable
+                    } else {
+                        SyncLevel::Undetectable
+                    };
+                    result.push(LipSync {
+                        cname: cname.clone(),
+                        remote: a.remote,
+                        dir: a.dir,
+                        audio_ssrc: a.ssrc,
+                        video_ssrc: v.ssrc,
+                        samples,
+                        max_lead,
+                        max_lag,
+                        level,
+                    });
+                }
+            }
+        }
+    }
+    result.sort_by(|a, b| a.cname.cmp(&b.cname).then(a.audio_ssrc.cmp(&b.audio_ssrc)));
+    result
+}
+
+fn measure_pair(
+    audio: &Stream,
+    video: &Stream,
+    mappings: &HashMap<u32, Vec<SrMapping>>,
+    clock_rates: &HashMap<u32, u32>,
+) -> Option<PairSync> {
+    let start = audio.pkts.first()?.ts.min(video.pkts.first()?.ts);
+    let audio_lateness = lateness_by_period(audio, start, &mappings[&audio.ssrc], clock_rates[&audio.ssrc]);
+    let video_lateness = lateness_by_period(video, start, &mappings[&video.ssrc], clock_rates[&video.ssrc]);
+    let mut samples = Vec::new();
+    for (period, a) in audio_lateness.iter() {
+        if let Some(v) = video_lateness.get(period) {
+            let ts = start + std::time::Duration::from_micros((*period as u128 * SAMPLE_PERIOD) as u64);
+            // video arriving later than its sender time than audio does: audio leads
+            samples.push((ts, v - a));
+        }
+    }
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by_key(|s| s.0);
+    let max_lead = samples.iter().map(|s| s.1).max().unwrap().max(0);
+    let max_lag = samples.iter().map(|s| s.1).min().unwrap().min(0);
+    Some((samples, max_lead, max_lag))
+}
+
+/// average (arrival - sender ntp time) of a stream per sample period, usec
+fn lateness_by_period(stream: &Stream, start: SystemTime, mappings: &[SrMapping], rate: u32) -> HashMap<u64, i64> {
+    let mut sums: HashMap<u64, (i64, i64)> = HashMap::new();
+    for p in stream.pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        // latest sender report seen before the packet
+        let idx = mappings.iter().rposition(|m| m.0 <= p.ts).unwrap_or(0);
+        let (_, ntp, sr_ts) = mappings[idx];
+        let sender_time = ntp + timestamp_delta(rtp.header.timestamp, sr_ts) * 1_000_000 / rate as i64;
+        let arrival = p.ts.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_micros() as i64;
+        let period = (p.ts.duration_since(start).unwrap_or_default().as_micros() / SAMPLE_PERIOD) as u64;
+        let e = sums.entry(period).or_insert((0, 0));
+        e.0 += arrival - sender_time;
+        e.1 += 1;
+    }
+    sums.into_iter().map(|(k, (sum, n))| (k, sum / n)).collect()
+}
+
+/// rtp clock rate of `ssrc` from its sender reports in `rtcp_pkts`
+pub fn sender_clock_rate(rtcp_pkts: &[FlowPacket], ssrc: u32) -> Option<u32> {
+    let mut mappings: Vec<SrMapping> = rtcp_pkts
+        .iter()
+        .flat_map(sr_mappings)
+        .filter(|(s, _)| *s == ssrc)
+        .map(|(_, m)| m)
+        .collect();
+    sort_mappings(&mut mappings);
+    estimate_clock_rate(&mappings)
+}
+
+/// order sender reports by ntp time, a report relayed more than once is kept
+/// at its first arrival
+fn sort_mappings(mappings: &mut Vec<SrMapping>) {
+    mappings.sort_by_key(|m| (m.1, m.0));
+    mappings.dedup_by_key(|m| m.1);
+}
+
+/// (sender ssrc, mapping) of every sender report in an rtcp packet
+fn sr_mappings(p: &FlowPacket) -> Vec<(u32, SrMapping)> {
+    let rtcp = match p.rtcp() {
+        Some(rtcp) => rtcp,
+        None => return vec![],
+    };
+    rtcp.sender_reports()
+        .map(|sr| {
+            let info = &sr.sender_info;
+            let ntp = info.ntp_sec as i64 * 1_000_000 + ((info.ntp_frac as i64 * 1_000_000) >> 32);
+            (sr.ssrc, (p.ts, ntp, info.rtp_timestamp))
+        })
+        .collect()
+}
+
+/// rtp clock rate from the first and last sender report by ntp time, snapped
+/// to a common rate
+fn estimate_clock_rate(mappings: &[SrMapping]) -> Option<u32> {
+    let (first, last) = (mappings.first()?, mappings.last()?);
+    let ntp_delta = last.1 - first.1;
+    if ntp_delta < 1_000_000 {
+        return None;
+    }
+    let rate = timestamp_delta(last.2, first.2) as f64 * 1e6 / ntp_delta as f64;
+    CLOCK_RATES
+        .iter()
+        .copied()
+        .min_by_key(|r| (*r as f64 - rate).abs() as u64)
+        .filter(|r| (*r as f64 - rate).abs() < *r as f64 * 0.05)
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, net::SocketAddr, time::{Duration, SystemTime}};
+
+use super::{FlowDirection, FlowPacket, connection::Connection};
+
+/// a contributor absent for longer than this starts a new appearance
+const APPEARANCE_GAP: Duration = Duration::from_secs(1);
+/// inter-packet time counted towards speaking durations is capped at this,
+/// so silence suppression gaps are not credited to anyone
+const MAX_PACKET_GAP: Duration = Duration::from_millis(200);
+
+pub struct Contributor {
+    pub csrc: u32,
+    pub pkts: usize,
+    /// (first, last) packet of each period the csrc was listed
+    pub appearances: Vec<(SystemTime, SystemTime)>,
+    /// time listed among the contributors, usec
+    pub present: u64,
+    /// time listed first, which mixers use for the loudest source, usec
+    pub active: u64,
+}
+/// Stream produced by a mixer, recognized by its CSRC lists
+pub struct MixedStream {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub pkts: usize,
+    /// packets carrying at least one csrc
+    pub mixed_pkts: usize,
+    pub contributors: Vec<Contributor>,
+    /// (since, csrc) every time the first listed csrc changes
+    pub speakers: Vec<(SystemTime, u32)>,
+}
+
+/// Report every stream that carries CSRCs, with who contributed to it and when.
+pub fn analyze(conn_list: &[Connection]) -> Vec<MixedStream> {
+    let mut result = Vec::new();
+    for conn in conn_list.iter() {
+        let streams = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in streams.iter() {
+            let ssrc = match ssrc {
+                Some(ssrc) => *ssrc,
+                None => continue,
+            };
+            if let Some(mixed) = analyze_stream(ssrc, *dir, conn.header.remote, pkts) {
+                result.push(mixed);
+            }
+        }
+    }
+    result
+}
+
+fn analyze_stream(ssrc: u32, dir: FlowDirection, remote: SocketAddr, pkts: &[FlowPacket]) -> Option<MixedStream> {
+    let mut contributors: HashMap<u32, Contributor> = HashMap::new();
+    let mut speakers: Vec<(SystemTime, u32)> = Vec::new();
+    let mut mixed_pkts = 0;
+    let mut last: Option<(SystemTime, &[u32])> = None;
+    for p in pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        let csrcs = &rtp.header.csrcs[..];
+        // credit the time since the previous packet to what it listed
+        if let Some((last_ts, last_csrcs)) = last {
+            let gap = p.ts.duration_since(last_ts).unwrap_or_default().min(MAX_PACKET_GAP);
+            let gap = gap.as_micros() as u64;
+            for (i, csrc) in last_csrcs.iter().enumerate() {
+                let c = contributors.get_mut(csrc).unwrap();
+                c.present += gap;
+                if i == 0 {
+                    c.active += gap;
+                }
+            }
+        }
+        last = Some((p.ts, csrcs));
+        if csrcs.is_empty() {
+            continue;
+        }
+        mixed_pkts += 1;
+        for csrc in csrcs.iter() {
+            let c = contributors.entry(*csrc).or_insert_with(|| Contributor {
+                csrc: *csrc,
+                pkts: 0,
+                appearances: vec![],
+                present: 0,
+                active: 0,
+            });
+            c.pkts += 1;
+            match c.appearances.last_mut() {
+                Some(a) if p.ts.duration_since(a.1).unwrap_or_default() <= APPEARANCE_GAP => a.1 = p.ts,
+                _ => c.appearances.push((p.ts, p.ts)),
+            }
+        }
+        if speakers.last().map(|s| s.1) != Some(csrcs[0]) {
+            speakers.push((p.ts, csrcs[0]));
+        }
+    }
+    if mixed_pkts == 0 {
+        return None;
+    }
+    let mut contributors: Vec<Contributor> = contributors.into_values().collect();
+    contributors.sort_by(|a, b| b.active.cmp(&a.active).then(b.present.cmp(&a.present)));
+    Some(MixedStream {
+        ssrc,
+        dir,
+        remote,
+        pkts: pkts.len(),
+        mixed_pkts,
+        contributors,
+        speakers,
+    })
+}
@@ -1,5 +1,27 @@
 pub mod connection;
 pub mod call;
+pub mod jitter_buffer;
+pub mod window;
+pub mod skew;
+pub mod congestion;
+pub mod timestamp;
+pub mod rtt;
+pub mod reception;
+pub mod feedback;
+pub mod lipsync;
+pub mod participant;
+pub mod lifecycle;
+pub mod conformance;
+pub mod sdp;
+pub mod mixer;
+pub mod audio_level;
+pub mod transport;
+pub mod bitrate;
+pub mod rtx;
+pub mod fec;
+pub mod simulcast;
+pub mod bundle;
+pub mod padding;
 
 use crate::structs::{ether::Ether, ip::IPHeader, ipv4::IPv4, raw::Raw, udp::UDP, rtp::RTP, rtcp::RTCP};
 use nom::bitvec::view::AsBits;
@@ -15,7 +37,7 @@ pub enum FlowType {
     Rtcp,
     Udp,
 }
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 pub enum FlowDirection {
     Ingress,
     Egress,
@@ -30,6 +52,9 @@ impl FlowPacket {
     pub fn rtp(&self) -> Option<&RTP> {
         self.payload.rtp()
     }
+    pub fn rtcp(&self) -> Option<&RTCP> {
+        self.payload.rtcp()
+    }
 }
 pub struct FlowHeader {
     ftype: FlowType,
@@ -53,6 +78,13 @@ impl FlowPayload {
             None
         }
     }
+    pub fn rtcp(&self) -> Option<&RTCP> {
+        if let FlowPayload::Rtcp(rtcp) = self {
+            Some(rtcp)
+        } else {
+            None
+        }
+    }
 }
 pub struct FlowData {
     ts: SystemTime,
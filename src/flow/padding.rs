@@ -0,0 +1,107 @@
+use std::{net::SocketAddr, time::{Duration, SystemTime}};
+
+use super::{FlowDirection, FlowPacket, connection::Connection};
+
+/// padding packets closer than this belong to the same burst
+const PROBE_GAP: Duration = Duration::from_millis(10);
+/// a burst of at least this many padding packets is a bandwidth probe
+const PROBE_MIN_PKTS: usize = 5;
+
+/// A burst of padding sent to probe the available bandwidth
+pub struct ProbeCluster {
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub pkts: usize,
+    /// on the wire, rtp header included
+    pub bytes: usize,
+    /// rate of the burst, bps
+    pub bitrate: u64,
+}
+/// Padding-only packets of a stream, reported apart from its media
+pub struct PaddingStats {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub media_pkts: usize,
+    pub padding_pkts: usize,
+    pub padding_bytes: usize,
+    /// padding packets not part of a probe
+    pub filler_pkts: usize,
+    pub clusters: Vec<ProbeCluster>,
+}
+
+/// Find padding-only packets in every stream and group the dense bursts
+/// of them into probe clusters. Takes every connection, probes are often
+/// sent on an RTX stream without a reverse direction.
+pub fn analyze(conns: &[&Connection]) -> Vec<PaddingStats> {
+    let mut result = Vec::new();
+    for conn in conns.iter() {
+        let list = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in list.iter() {
+            let ssrc = match ssrc {
+                Some(ssrc) => *ssrc,
+                None => continue,
+            };
+            let padding: Vec<(SystemTime, usize)> = pkts
+                .iter()
+                .filter_map(|p| p.rtp().filter(|rtp| rtp.padding_only()).map(|rtp| (p.ts, rtp.len)))
+                .collect();
+            if padding.is_empty() {
+                continue;
+            }
+            let clusters = clusters(&padding);
+            let probed: usize = clusters.iter().map(|c| c.pkts).sum();
+            result.push(PaddingStats {
+                ssrc,
+                dir: *dir,
+                remote: conn.header.remote,
+                media_pkts: pkts.iter().filter(|p| is_media(p)).count(),
+                padding_pkts: padding.len(),
+                padding_bytes: padding.iter().map(|p| p.1).sum(),
+                filler_pkts: padding.len() - probed,
+                clusters,
+            });
+        }
+    }
+    result
+}
+
+/// rtp carrying media, padding-only packets excluded
+pub fn is_media(pkt: &FlowPacket) -> bool {
+    pkt.rtp().is_some_and(|rtp| !rtp.padding_only())
+}
+
+fn clusters(padding: &[(SystemTime, usize)]) -> Vec<ProbeCluster> {
+    let mut bursts: Vec<&[(SystemTime, usize)]> = Vec::new();
+    let mut first = 0;
+    for i in 1..=padding.len() {
+        let split = i == padding.len() || padding[i].0.duration_since(padding[i - 1].0).unwrap_or_default() > PROBE_GAP;
+        if split {
+            bursts.push(&padding[first..i]);
+            first = i;
+        }
+    }
+    bursts
+        .into_iter()
+        .filter(|b| b.len() >= PROBE_MIN_PKTS)
+        .map(|b| {
+            let (start, end) = (b[0].0, b[b.len() - 1].0);
+            let bytes: usize = b.iter().map(|p| p.1).sum();
+            // the last packet ends the burst, its bytes don't count in the span
+            let span = end.duration_since(start).unwrap_or_default().as_micros() as u64;
+            let bitrate = ((bytes - b[b.len() - 1].1) as u64 * 8 * 1_000_000)
+                .checked_div(span)
+                .unwrap_or(0);
+            ProbeCluster {
+                start,
+                end,
+                pkts: b.len(),
+                bytes,
+                bitrate,
+            }
+        })
+        .collect()
+}
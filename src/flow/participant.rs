@@ -0,0 +1,121 @@
+use std::{collections::{BTreeSet, HashMap}, net::SocketAddr};
+
+use crate::structs::rtcp::{RtcpPacket, SdesItem};
+
+use super::{FlowDirection, connection::Connection};
+
+/// SDES items announced for one ssrc
+#[derive(Default, Clone)]
+pub struct SourceDescription {
+    pub cname: Option<String>,
+    pub name: Option<String>,
+    pub tool: Option<String>,
+}
+pub struct ParticipantStream {
+    pub ssrc: u32,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub pkts: usize,
+    pub payload_types: BTreeSet<u8>,
+}
+/// All streams sent by one endpoint, identified by its CNAME
+pub struct Participant {
+    pub cname: String,
+    pub name: Option<String>,
+    pub tool: Option<String>,
+    pub streams: Vec<ParticipantStream>,
+}
+
+/// SDES items of every ssrc seen in rtcp, in either direction
+pub fn collect_sdes(conn_list: &[Connection]) -> HashMap<u32, SourceDescription> {
+    let mut sdes: HashMap<u32, SourceDescription> = HashMap::new();
+    for conn in conn_list.iter() {
+        for p in conn.ingress_rtcp.iter().chain(conn.egress_rtcp.iter()) {
+            let rtcp = match p.rtcp() {
+                Some(rtcp) => rtcp,
+                None => continue,
+            };
+            for packet in rtcp.packets.iter() {
+                let chunks = match packet {
+                    RtcpPacket::SourceDescription(chunks) => chunks,
+                    _ => continue,
+                };
+                for chunk in chunks.iter() {
+                    let desc = sdes.entry(chunk.ssrc).or_default();
+                    for item in chunk.items.iter() {
+                        match item {
+                            SdesItem::Cname(s) => desc.cname = Some(s.clone()),
+                            SdesItem::Name(s) => desc.name = Some(s.clone()),
+                            SdesItem::Tool(s) => desc.tool = Some(s.clone()),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    sdes
+}
+
+/// Group the streams each endpoint sends by SDES CNAME. Streams without SDES
+/// of their own (RTX, simulcast layers) join the participant sending other
+/// streams from the same remote address.
+pub fn extract_participants(conn_list: &[Connection]) -> Vec<Participant> {
+    let sdes = collect_sdes(conn_list);
+    let cname_of = |ssrc: u32| sdes.get(&ssrc).and_then(|d| d.cname.clone());
+
+    // remote address => cname, learned from streams with SDES
+    let mut remote_cnames: HashMap<SocketAddr, String> = HashMap::new();
+    for conn in conn_list.iter() {
+        if let Some(cname) = conn.ingress_ssrc().and_then(cname_of) {
+            remote_cnames.insert(conn.header.remote, cname);
+        }
+    }
+
+    let mut participants: Vec<Participant> = Vec::new();
+    for conn in conn_list.iter() {
+        let streams = [
+            (conn.ingress_ssrc(), FlowDirection::Ingress, &conn.ingress_pkts),
+            (conn.egress_ssrc(), FlowDirection::Egress, &conn.egress_pkts),
+        ];
+        for (ssrc, dir, pkts) in streams.iter() {
+            let ssrc = match ssrc {
+                Some(ssrc) => *ssrc,
+                None => continue,
+            };
+            let cname = match cname_of(ssrc) {
+                Some(cname) => cname,
+                None if *dir == FlowDirection::Ingress => match remote_cnames.get(&conn.header.remote) {
+                    Some(cname) => cname.clone(),
+                    None => continue,
+                },
+                None => continue,
+            };
+            let idx = match participants.iter().position(|p| p.cname == cname) {
+                Some(idx) => idx,
+                None => {
+                    participants.push(Participant {
+                        cname: cname.clone(),
+                        name: None,
+                        tool: None,
+                        streams: vec![],
+                    });
+                    participants.len() - 1
+                }
+            };
+            let participant = &mut participants[idx];
+            if let Some(desc) = sdes.get(&ssrc) {
+                participant.name = participant.name.take().or_else(|| desc.name.clone());
+                participant.tool = participant.tool.take().or_else(|| desc.tool.clone());
+            }
+            participant.streams.push(ParticipantStream {
+                ssrc,
+                dir: *dir,
+                remote: conn.header.remote,
+                pkts: pkts.len(),
+                payload_types: pkts.iter().filter_map(|p| p.rtp()).map(|r| r.header.payload).collect(),
+            });
+        }
+    }
+    participants
+}
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use crate::structs::rtcp::{VoipMetrics, XrBlock};
+
+use super::{FlowPacket, call::{seq_delta, timestamp_to_usec, InterarrivalJitter}};
+
+/// Endpoint reported reception statistics of a stream, lined up against the
+/// same stream measured at the capture point.
+pub struct ReceptionComparison {
+    pub ssrc: u32,
+    pub reports: usize,
+    /// last report, cumulative over the seq range it covers
+    pub reported_lost: i64,
+    pub reported_lost_rate: f64,
+    /// missing at egress of the capture point over the same seq range
+    pub egress_lost: u64,
+    pub egress_lost_rate: f64,
+    /// missing at ingress of the capture point over the same seq range
+    pub upstream_lost: u64,
+    pub upstream_lost_rate: f64,
+    /// interarrival jitter, usec
+    pub reported_jitter: u64,
+    pub egress_jitter: u64,
+    pub upstream_jitter: u64,
+}
+
+impl ReceptionComparison {
+    /// packets the endpoint lost after they left the capture point
+    pub fn downstream_lost(&self) -> i64 {
+        self.reported_lost - self.egress_lost as i64
+    }
+}
+
+/// `report_pkts` are the rtcp packets sent by the receiving endpoint of
+/// `egress_pkts`, `ingress_pkts` is the same stream before forwarding.
+pub fn compare(
+    report_pkts: &[FlowPacket],
+    egress_pkts: &[FlowPacket],
+    ingress_pkts: &[FlowPacket],
+    clock_rate: u32,
+) -> Option<ReceptionComparison> {
+    let first = egress_pkts.iter().find_map(|p| p.rtp())?;
+    let ssrc = first.header.ssrc;
+    let base_seq = first.header.seqnum;
+
+    let mut reports = 0;
+    let mut last = None;
+    for p in report_pkts.iter() {
+        if let Some(rtcp) = p.rtcp() {
+            for (_, block) in rtcp.report_blocks() {
+                if block.ssrc == ssrc {
+                    reports += 1;
+                    last = Some(block.clone());
+                }
+            }
+        }
+    }
+    let last = last?;
+    // extended highest seq counts cycles from the endpoint's first packet,
+    // which is the first forwarded packet
+    let highest = last.highest_seq as i64 - base_seq as i64;
+    if highest < 0 {
+        return None;
+    }
+    let expected = highest as u64 + 1;
+    let (egress_lost, egress_jitter) = measure(egress_pkts, ssrc, base_seq, highest, clock_rate);
+    let (upstream_lost, upstream_jitter) = measure(ingress_pkts, ssrc, base_seq, highest, clock_rate);
+    let rate = |lost: i64| lost as f64 / expected as f64 * 100.0;
+    Some(ReceptionComparison {
+        ssrc,
+        reports,
+        reported_lost: last.cumulative_lost as i64,
+        reported_lost_rate: rate(last.cumulative_lost as i64),
+        egress_lost,
+        egress_lost_rate: rate(egress_lost as i64),
+        upstream_lost,
+        upstream_lost_rate: rate(upstream_lost as i64),
+        reported_jitter: timestamp_to_usec(last.jitter as i64, clock_rate) as u64,
+        egress_jitter,
+        upstream_jitter,
+    })
+}
+
+/// latest RTCP XR VoIP metrics the endpoint reported about stream `ssrc`
+pub fn last_voip_metrics(report_pkts: &[FlowPacket], ssrc: u32) -> Option<VoipMetrics> {
+    report_pkts
+        .iter()
+        .rev()
+        .filter_map(|p| p.rtcp())
+        .flat_map(|rtcp| rtcp.xr_blocks())
+        .find_map(|b| match b {
+            XrBlock::VoipMetrics(m) if m.ssrc == ssrc => Some(m.clone()),
+            _ => None,
+        })
+}
+
+/// return (lost packets in seq range [0, highest] relative to `base_seq`, jitter)
+fn measure(pkts: &[FlowPacket], ssrc: u32, base_seq: u16, highest: i64, clock_rate: u32) -> (u64, u64) {
+    let mut jitter = InterarrivalJitter::new(clock_rate);
+    let mut received = HashSet::new();
+    let mut last_seq: Option<(u16, i64)> = None;
+    for p in pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) if rtp.header.ssrc == ssrc => rtp,
+            _ => continue,
+        };
+        let seq = match last_seq {
+            Some((raw, ext)) => ext + seq_delta(rtp.header.seqnum, raw),
+            None => seq_delta(rtp.header.seqnum, base_seq),
+        };
+        last_seq = Some((rtp.header.seqnum, seq));
+        if seq > highest {
+            continue;
+        }
+        jitter.update(p);
+        if seq >= 0 {
+            received.insert(seq);
+        }
+    }
+    ((highest as u64 + 1).saturating_sub(received.len() as u64), jitter.value())
+}
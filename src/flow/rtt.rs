@@ -0,0 +1,61 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use super::FlowPacket;
+
+pub struct RttStats {
+    pub min: u64,
+    pub avg: u64,
+    pub max: u64,
+    /// (report arrival, rtt usec)
+    pub samples: Vec<(SystemTime, u64)>,
+}
+
+/// Compute round-trip time between capture point and a remote endpoint, from
+/// the SRs seen in `sr_pkts` and the LSR/DLSR echoed back in `report_pkts`.
+pub fn compute_rtt(sr_pkts: &[FlowPacket], report_pkts: &[FlowPacket]) -> Option<RttStats> {
+    // (ssrc, middle 32 bits of ntp) => SR capture time
+    let mut sr_map = HashMap::new();
+    for p in sr_pkts.iter() {
+        if let Some(rtcp) = p.rtcp() {
+            for sr in rtcp.sender_reports() {
+                sr_map.insert((sr.ssrc, sr.sender_info.ntp_middle()), p.ts);
+            }
+        }
+    }
+
+    let mut samples = Vec::new();
+    for p in report_pkts.iter() {
+        let rtcp = match p.rtcp() {
+            Some(rtcp) => rtcp,
+            None => continue,
+        };
+        for (_, block) in rtcp.report_blocks() {
+            if block.lsr == 0 {
+                continue;
+            }
+            if let Some(sr_ts) = sr_map.get(&(block.ssrc, block.lsr)) {
+                let elapsed = match p.ts.duration_since(*sr_ts) {
+                    Ok(d) => d.as_micros() as u64,
+                    Err(_) => continue,
+                };
+                // dlsr in 1/65536 seconds
+                let dlsr = block.dlsr as u64 * 1_000_000 / 65536;
+                if elapsed >= dlsr {
+                    samples.push((p.ts, elapsed - dlsr));
+                }
+            }
+        }
+    }
+    if samples.is_empty() {
+        return None;
+    }
+    let min = samples.iter().map(|s| s.1).min().unwrap();
+    let max = samples.iter().map(|s| s.1).max().unwrap();
+    let avg = samples.iter().map(|s| s.1).sum::<u64>() / samples.len() as u64;
+    Some(RttStats {
+        min,
+        avg,
+        max,
+        samples,
+    })
+}
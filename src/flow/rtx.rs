@@ -0,0 +1,225 @@
+use std::{collections::{HashMap, HashSet}, net::SocketAddr, time::SystemTime};
+
+use crate::structs::rtcp::{RtcpPacket, TransportFeedback};
+
+use super::{FlowDirection, FlowPacket, call::seq_delta, connection::Connection, sdp::SdpInfo};
+
+/// rtx packets checked when looking for the primary stream by OSN
+const OSN_PROBE_PKTS: usize = 50;
+/// an original packet is looked for this many seqs around the OSN
+const OSN_WINDOW: i64 = 64;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Association {
+    /// a=ssrc-group:FID
+    SsrcGroup,
+    /// rtx payload type with apt=
+    PayloadType,
+    /// original seq and timestamp matched against the primary stream
+    Osn,
+}
+/// RFC 4588 retransmission of a primary stream, measured at the capture point
+pub struct RtxStats {
+    pub primary_ssrc: u32,
+    pub rtx_ssrc: u32,
+    pub rtx_pt: u8,
+    pub dir: FlowDirection,
+    pub remote: SocketAddr,
+    pub association: Association,
+    pub rtx_pkts: usize,
+    /// distinct seqs requested by NACK
+    pub requested: usize,
+    /// requested seqs that were retransmitted
+    pub retransmitted: usize,
+    /// primary loss before repair
+    pub lost: usize,
+    /// lost seqs filled in by retransmission
+    pub recovered: usize,
+    pub residual_lost: usize,
+    pub residual_lost_rate: f64,
+    /// NACK to retransmission, usec
+    pub latency_avg: Option<u64>,
+    pub latency_max: Option<u64>,
+}
+
+struct Stream<'a> {
+    ssrc: u32,
+    dir: FlowDirection,
+    remote: SocketAddr,
+    pt: u8,
+    pkts: &'a [FlowPacket],
+    /// rtcp from the receiver of the stream
+    feedback: &'a [FlowPacket],
+}
+
+/// Pair RTX streams with their primary streams, from SDP when available and
+/// from the OSN otherwise, and measure how well retransmission repairs loss.
+/// Takes every connection, RTX often lives in one without a reverse direction.
+pub fn analyze(conns: &[&Connection], sdp: &SdpInfo) -> Vec<RtxStats> {
+    let mut streams = Vec::new();
+    for conn in conns.iter() {
+        let list = [
+            (FlowDirection::Ingress, &conn.ingress_pkts, &conn.egress_rtcp),
+            (FlowDirection::Egress, &conn.egress_pkts, &conn.ingress_rtcp),
+        ];
+        for (dir, pkts, feedback) in list.iter() {
+            if let Some(rtp) = pkts.first().and_then(|p| p.rtp()) {
+                streams.push(Stream {
+                    ssrc: rtp.header.ssrc,
+                    dir: *dir,
+                    remote: conn.header.remote,
+                    pt: rtp.header.payload,
+                    pkts,
+                    feedback,
+                });
+            }
+        }
+    }
+
+    let fid: HashMap<u32, u32> = sdp
+        .ssrc_groups
+        .iter()
+        .filter(|(semantics, ssrcs)| semantics == "FID" && ssrcs.len() == 2)
+        .map(|(_, ssrcs)| (ssrcs[1], ssrcs[0]))
+        .collect();
+    let apt: HashMap<u8, u8> = sdp
+        .payload_types("rtx")
+        .into_iter()
+        .filter_map(|pt| Some((pt, sdp.fmtp_param(pt, "apt")?.parse().ok()?)))
+        .collect();
+
+    let mut result = Vec::new();
+    for rtx in streams.iter() {
+        let mut candidates = streams
+            .iter()
+            .filter(|s| s.ssrc != rtx.ssrc && s.dir == rtx.dir && s.remote.ip() == rtx.remote.ip());
+        let found = if let Some(primary) = fid.get(&rtx.ssrc) {
+            candidates.find(|s| s.ssrc == *primary).map(|s| (s, Association::SsrcGroup))
+        } else if let Some(apt) = apt.get(&rtx.pt) {
+            candidates
+                .filter(|s| s.pt == *apt)
+                .max_by_key(|s| osn_matches(rtx, s))
+                .map(|s| (s, Association::PayloadType))
+        } else {
+            candidates
+                .map(|s| (s, osn_matches(rtx, s)))
+                .filter(|(_, n)| *n >= 3 && *n * 2 >= rtx.pkts.len().min(OSN_PROBE_PKTS))
+                .max_by_key(|(_, n)| *n)
+                .map(|(s, _)| (s, Association::Osn))
+        };
+        if let Some((primary, association)) = found {
+            result.push(measure(primary, rtx, association));
+        }
+    }
+    result
+}
+
+/// rtx packets whose OSN and timestamp fit a packet of `primary`
+fn osn_matches(rtx: &Stream, primary: &Stream) -> usize {
+    let seqs: HashMap<u32, u16> = primary
+        .pkts
+        .iter()
+        .filter_map(|p| p.rtp())
+        .map(|rtp| (rtp.header.timestamp, rtp.header.seqnum))
+        .collect();
+    rtx.pkts
+        .iter()
+        .filter_map(|p| p.rtp())
+        .take(OSN_PROBE_PKTS)
+        .filter(|rtp| match (osn(&rtp.payload), seqs.get(&rtp.header.timestamp)) {
+            (Some(osn), Some(seq)) => seq_delta(osn, *seq).abs() < OSN_WINDOW,
+            _ => false,
+        })
+        .count()
+}
+
+fn osn(payload: &[u8]) -> Option<u16> {
+    if payload.len() < 2 {
+        return None;
+    }
+    Some((payload[0] as u16) << 8 | payload[1] as u16)
+}
+
+fn measure(primary: &Stream, rtx: &Stream, association: Association) -> RtxStats {
+    // extended seqs of the primary stream as they pass the capture point
+    let mut timeline: Vec<(SystemTime, i64)> = Vec::new();
+    for p in primary.pkts.iter() {
+        if let Some(rtp) = p.rtp() {
+            let seq = match timeline.last() {
+                Some((_, last)) => last + seq_delta(rtp.header.seqnum, *last as u16),
+                None => rtp.header.seqnum as i64,
+            };
+            timeline.push((p.ts, seq));
+        }
+    }
+    let received: HashSet<i64> = timeline.iter().map(|t| t.1).collect();
+    // seqs carried by nack and rtx are extended around the latest primary seq
+    let extend = |ts: SystemTime, seq: u16| {
+        let idx = timeline.partition_point(|t| t.0 <= ts).max(1) - 1;
+        let reference = timeline.get(idx).map(|t| t.1).unwrap_or(seq as i64);
+        reference + seq_delta(seq, reference as u16)
+    };
+
+    let mut requested: HashMap<i64, SystemTime> = HashMap::new();
+    for p in primary.feedback.iter() {
+        let rtcp = match p.rtcp() {
+            Some(rtcp) => rtcp,
+            None => continue,
+        };
+        for packet in rtcp.packets.iter() {
+            if let RtcpPacket::TransportFeedback(TransportFeedback::Nack(nack)) = packet {
+                if nack.media_ssrc == primary.ssrc {
+                    for seq in nack.lost.iter() {
+                        requested.entry(extend(p.ts, *seq)).or_insert(p.ts);
+                    }
+                }
+            }
+        }
+    }
+    let mut retransmitted: HashMap<i64, SystemTime> = HashMap::new();
+    let mut rtx_pkts = 0;
+    for p in rtx.pkts.iter() {
+        if let Some(osn) = p.rtp().and_then(|rtp| osn(&rtp.payload)) {
+            rtx_pkts += 1;
+            retransmitted.entry(extend(p.ts, osn)).or_insert(p.ts);
+        }
+    }
+
+    let latencies: Vec<u64> = requested
+        .iter()
+        .filter_map(|(seq, nack_ts)| retransmitted.get(seq)?.duration_since(*nack_ts).ok())
+        .map(|d| d.as_micros() as u64)
+        .collect();
+    let (first, last) = match (received.iter().min(), received.iter().max()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => (0, -1),
+    };
+    let expected = (last - first + 1).max(0) as usize;
+    let missing: Vec<i64> = (first..=last).filter(|seq| !received.contains(seq)).collect();
+    let recovered = missing.iter().filter(|seq| retransmitted.contains_key(seq)).count();
+    let residual_lost = missing.len() - recovered;
+    RtxStats {
+        primary_ssrc: primary.ssrc,
+        rtx_ssrc: rtx.ssrc,
+        rtx_pt: rtx.pt,
+        dir: primary.dir,
+        remote: primary.remote,
+        association,
+        rtx_pkts,
+        requested: requested.len(),
+        retransmitted: requested.keys().filter(|seq| retransmitted.contains_key(seq)).count(),
+        lost: missing.len(),
+        recovered,
+        residual_lost,
+        residual_lost_rate: if expected > 0 {
+            residual_lost as f64 / expected as f64 * 100.0
+        } else {
+            0.0
+        },
+        latency_avg: match latencies.len() {
+            0 => None,
+            n => Some(latencies.iter().sum::<u64>() / n as u64),
+        },
+        latency_max: latencies.iter().max().copied(),
+    }
+}
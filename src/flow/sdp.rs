@@ -0,0 +1,203 @@
+use crate::errors::*;
+use std::{collections::HashMap, str::FromStr};
+
+use super::{FlowData, FlowPayload};
+
+/// `ID=URI` header extension mapping given on the command line
+#[derive(Debug, Clone)]
+pub struct ExtMapEntry {
+    pub id: u8,
+    pub uri: String,
+}
+
+impl FromStr for ExtMapEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (id, uri) = match s.find('=') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => bail!("Expected ID=URI: {:?}", s),
+        };
+        let id = id.parse().with_context(|| format!("Invalid extension id: {:?}", id))?;
+        Ok(ExtMapEntry {
+            id,
+            uri: uri.to_string(),
+        })
+    }
+}
+
+/// `PT=NAME[/RATE]` payload type mapping given on the command line
+#[derive(Debug, Clone)]
+pub struct RtpMapEntry {
+    pub pt: u8,
+    pub name: String,
+    pub clock_rate: Option<u32>,
+}
+
+impl FromStr for RtpMapEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (pt, name) = match s.find('=') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => bail!("Expected PT=NAME[/RATE]: {:?}", s),
+        };
+        let pt = pt.parse().with_context(|| format!("Invalid payload type: {:?}", pt))?;
+        let (name, clock_rate) = match name.find('/') {
+            Some(idx) => {
+                let rate = &name[idx + 1..];
+                let rate: u32 = rate.parse().with_context(|| format!("Invalid clock rate: {:?}", rate))?;
+                if rate == 0 {
+                    bail!("Clock rate must not be 0: {:?}", s);
+                }
+                (&name[..idx], Some(rate))
+            }
+            None => (name, None),
+        };
+        Ok(RtpMapEntry {
+            pt,
+            name: name.to_lowercase(),
+            clock_rate,
+        })
+    }
+}
+
+/// Session parameters learned from SDP seen in the capture, seeded by the
+/// command line.
+#[derive(Default)]
+pub struct SdpInfo {
+    /// header extension id => uri
+    pub extmap: HashMap<u8, String>,
+    /// header extension id => extension attributes, e.g. `vad=on`
+    pub extmap_attributes: HashMap<u8, String>,
+    /// payload type => lowercase encoding name
+    pub rtpmap: HashMap<u8, String>,
+    /// payload type => rtp clock rate
+    pub clock_rates: HashMap<u8, u32>,
+    /// payload type => format parameters
+    pub fmtp: HashMap<u8, String>,
+    /// (semantics, ssrcs) of a=ssrc-group
+    pub ssrc_groups: Vec<(String, Vec<u32>)>,
+}
+
+impl SdpInfo {
+    pub fn new(extmap: &[ExtMapEntry], rtpmap: &[RtpMapEntry]) -> Self {
+        Self {
+            extmap: extmap.iter().map(|e| (e.id, e.uri.clone())).collect(),
+            rtpmap: rtpmap.iter().map(|e| (e.pt, e.name.clone())).collect(),
+            clock_rates: rtpmap.iter().filter_map(|e| Some((e.pt, e.clock_rate?))).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// pick up SDP attributes from a text payload, e.g. a SIP message body
+    pub fn learn(&mut self, data: &FlowData) {
+        let text = match data.payload {
+            FlowPayload::Text(ref text) => text,
+            _ => return,
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(value) = line.strip_prefix("a=extmap:") {
+                self.learn_extmap(value);
+            } else if let Some(value) = line.strip_prefix("a=rtpmap:") {
+                self.learn_rtpmap(value);
+            } else if let Some(value) = line.strip_prefix("a=fmtp:") {
+                self.learn_fmtp(value);
+            } else if let Some(value) = line.strip_prefix("a=ssrc-group:") {
+                self.learn_ssrc_group(value);
+            }
+        }
+    }
+
+    /// `a=extmap:<id>[/<direction>] <uri> [<attributes>]`
+    fn learn_extmap(&mut self, value: &str) {
+        let mut fields = value.split_whitespace();
+        let id = fields.next().and_then(|f| f.split('/').next()).and_then(|id| id.parse().ok());
+        if let (Some(id), Some(uri)) = (id, fields.next()) {
+            self.extmap.insert(id, uri.to_string());
+            let attributes: Vec<&str> = fields.collect();
+            if attributes.is_empty() {
+                self.extmap_attributes.remove(&id);
+            } else {
+                self.extmap_attributes.insert(id, attributes.join(" "));
+            }
+        }
+    }
+
+    /// `a=rtpmap:<pt> <encoding>/<clock rate>[/<channels>]`
+    fn learn_rtpmap(&mut self, value: &str) {
+        let mut fields = value.split_whitespace();
+        let pt = fields.next().and_then(|pt| pt.parse().ok());
+        let mut encoding = fields.next().map(|f| f.split('/')).into_iter().flatten();
+        // a zero rate is ignored, the static or default rate applies instead
+        let rate = |r: &str| r.parse().ok().filter(|r: &u32| *r > 0);
+        let (name, rate) = (encoding.next(), encoding.next().and_then(rate));
+        if let (Some(pt), Some(name)) = (pt, name) {
+            self.rtpmap.insert(pt, name.to_lowercase());
+            if let Some(rate) = rate {
+                self.clock_rates.insert(pt, rate);
+            }
+        }
+    }
+
+    /// `a=fmtp:<pt> <parameters>`
+    fn learn_fmtp(&mut self, value: &str) {
+        let mut fields = value.splitn(2, ' ');
+        let pt = fields.next().and_then(|pt| pt.parse().ok());
+        if let (Some(pt), Some(params)) = (pt, fields.next()) {
+            self.fmtp.insert(pt, params.trim().to_string());
+        }
+    }
+
+    /// `a=ssrc-group:<semantics> <ssrc> ...`
+    fn learn_ssrc_group(&mut self, value: &str) {
+        let mut fields = value.split_whitespace();
+        let semantics = match fields.next() {
+            Some(semantics) => semantics.to_string(),
+            None => return,
+        };
+        let ssrcs: Vec<u32> = fields.filter_map(|f| f.parse().ok()).collect();
+        let group = (semantics, ssrcs);
+        if !self.ssrc_groups.contains(&group) {
+            self.ssrc_groups.push(group);
+        }
+    }
+
+    /// clock rate of a payload type, from the rtpmap or the RFC 3551 static
+    /// payload types
+    pub fn clock_rate(&self, pt: u8) -> Option<u32> {
+        if let Some(rate) = self.clock_rates.get(&pt) {
+            return Some(*rate);
+        }
+        match pt {
+            0 | 3 | 4 | 5 | 7 | 8 | 9 | 12 | 13 | 15 | 18 => Some(8_000),
+            6 => Some(16_000),
+            10 | 11 => Some(44_100),
+            16 => Some(11_025),
+            17 => Some(22_050),
+            14 | 25 | 26 | 28 | 31 | 32 | 33 | 34 => Some(90_000),
+            _ => None,
+        }
+    }
+
+    /// payload types with the given encoding name
+    pub fn payload_types(&self, name: &str) -> Vec<u8> {
+        self.rtpmap.iter().filter(|(_, n)| n.as_str() == name).map(|(pt, _)| *pt).collect()
+    }
+
+    /// `name=value` from the format parameters of a payload type
+    pub fn fmtp_param(&self, pt: u8, name: &str) -> Option<&str> {
+        self.fmtp.get(&pt)?.split(';').find_map(|param| {
+            let mut kv = param.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k == name => Some(v),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn extension_id(&self, uri: &str) -> Option<u8> {
+        self.extmap.iter().find(|(_, u)| u.as_str() == uri).map(|(id, _)| *id)
+    }
+}
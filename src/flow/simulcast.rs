@@ -0,0 +1,280 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+use super::{FlowPacket, connection::Connection, sdp::SdpInfo};
+
+pub const MID: &str = "urn:ietf:params:rtp-hdrext:sdes:mid";
+pub const RID: &str = "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id";
+/// layers forwarded to a subscriber are evaluated over this period
+const LAYER_WINDOW: Duration = Duration::from_secs(1);
+
+/// What a subscriber receives: the simulcast encoding it comes from and the
+/// highest SVC layers in it
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Layer {
+    pub encoding: Option<String>,
+    pub spatial: Option<u8>,
+    pub temporal: Option<u8>,
+}
+/// One simulcast encoding sent by a publisher
+pub struct SimulcastStream {
+    pub ssrc: u32,
+    pub remote: SocketAddr,
+    pub mid: Option<String>,
+    /// rid, or the position in the SIM ssrc group
+    pub encoding: String,
+    pub pkts: usize,
+}
+pub struct Subscriber {
+    pub remote: SocketAddr,
+    pub ssrcs: Vec<u32>,
+    /// (since, layer) every time the forwarded layer changes
+    pub switches: Vec<(SystemTime, Layer)>,
+}
+
+/// Find the simulcast encodings publishers send, by RID/MID extensions or SIM
+/// ssrc groups, and follow which encoding and SVC layers each subscriber gets.
+/// Forwarded packets are traced back to their encoding by ssrc, by RID, or by
+/// payload when the SFU rewrites ssrc and seq.
+pub fn analyze(conns: &[&Connection], sdp: &SdpInfo) -> (Vec<SimulcastStream>, Vec<Subscriber>) {
+    let mid_id = sdp.extension_id(MID);
+    let rid_id = sdp.extension_id(RID);
+    let sim: HashMap<u32, usize> = sdp
+        .ssrc_groups
+        .iter()
+        .filter(|(semantics, _)| semantics == "SIM")
+        .flat_map(|(_, ssrcs)| ssrcs.iter().enumerate().map(|(i, ssrc)| (*ssrc, i)))
+        .collect();
+    let ext_str = |pkt: &FlowPacket, id: Option<u8>| {
+        let data = pkt.rtp()?.header.extension(id?)?;
+        Some(String::from_utf8_lossy(data).into_owned())
+    };
+
+    let mut sources = Vec::new();
+    let mut by_ssrc: HashMap<u32, String> = HashMap::new();
+    let mut by_payload: HashMap<u64, String> = HashMap::new();
+    for conn in conns.iter() {
+        let ssrc = match conn.ingress_ssrc() {
+            Some(ssrc) => ssrc,
+            None => continue,
+        };
+        let rid = conn.ingress_pkts.iter().find_map(|p| ext_str(p, rid_id));
+        let encoding = match (rid, sim.get(&ssrc)) {
+            (Some(rid), _) => rid,
+            (None, Some(i)) => format!("sim{}", i),
+            _ => continue,
+        };
+        for p in conn.ingress_pkts.iter() {
+            if let Some(rtp) = p.rtp() {
+                by_payload.insert(payload_hash(&rtp.payload), encoding.clone());
+            }
+        }
+        by_ssrc.insert(ssrc, encoding.clone());
+        sources.push(SimulcastStream {
+            ssrc,
+            remote: conn.header.remote,
+            mid: conn.ingress_pkts.iter().find_map(|p| ext_str(p, mid_id)),
+            encoding,
+            pkts: conn.ingress_pkts.len(),
+        });
+    }
+
+    // everything sent to a subscriber, across ssrc changes
+    let mut egress: HashMap<SocketAddr, Vec<&FlowPacket>> = HashMap::new();
+    for conn in conns.iter() {
+        egress.entry(conn.header.remote).or_default().extend(conn.egress_pkts.iter());
+    }
+    let mut subscribers = Vec::new();
+    for (remote, mut pkts) in egress.into_iter() {
+        pkts.sort_by_key(|p| p.ts);
+        let mut ssrcs: Vec<u32> = Vec::new();
+        let mut switches: Vec<(SystemTime, Layer)> = Vec::new();
+        let mut window: Option<(SystemTime, Layer, HashMap<String, usize>)> = None;
+        let mut known = false;
+        for p in pkts.iter() {
+            let rtp = match p.rtp() {
+                Some(rtp) => rtp,
+                None => continue,
+            };
+            if !ssrcs.contains(&rtp.header.ssrc) {
+                ssrcs.push(rtp.header.ssrc);
+            }
+            let encoding = by_ssrc
+                .get(&rtp.header.ssrc)
+                .cloned()
+                .or_else(|| ext_str(p, rid_id))
+                .or_else(|| by_payload.get(&payload_hash(&rtp.payload)).cloned());
+            let codec = sdp.rtpmap.get(&rtp.header.payload).map(|n| n.as_str());
+            let svc = svc_layers(codec, &rtp.payload);
+            known |= encoding.is_some() || svc.is_some();
+
+            if let Some((start, _, _)) = window {
+                if p.ts.duration_since(start).unwrap_or_default() >= LAYER_WINDOW {
+                    close_window(window.take().unwrap(), &mut switches);
+                }
+            }
+            let w = window.get_or_insert_with(|| (p.ts, Layer::default(), HashMap::new()));
+            if let Some(encoding) = encoding {
+                *w.2.entry(encoding).or_default() += 1;
+            }
+            if let Some((spatial, temporal)) = svc {
+                w.1.spatial = w.1.spatial.max(spatial);
+                w.1.temporal = w.1.temporal.max(temporal);
+            }
+        }
+        if let Some(w) = window {
+            close_window(w, &mut switches);
+        }
+        if known {
+            subscribers.push(Subscriber { remote, ssrcs, switches });
+        }
+    }
+    subscribers.sort_by_key(|s| s.remote);
+    (sources, subscribers)
+}
+
+/// the encoding of a window is the one most of its packets came from
+fn close_window(window: (SystemTime, Layer, HashMap<String, usize>), switches: &mut Vec<(SystemTime, Layer)>) {
+    let (start, mut layer, encodings) = window;
+    layer.encoding = encodings.into_iter().max_by_key(|(_, n)| *n).map(|(e, _)| e);
+    if switches.last().map(|s| &s.1) != Some(&layer) {
+        switches.push((start, layer));
+    }
+}
+
+fn payload_hash(payload: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    payload.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// (spatial, temporal) layer ids from the codec payload descriptor
+fn svc_layers(codec: Option<&str>, payload: &[u8]) -> Option<(Option<u8>, Option<u8>)> {
+    match codec? {
+        "vp8" => Some((None, Some(vp8_temporal(payload)?))),
+        "vp9" => vp9_layers(payload).map(|(s, t)| (Some(s), Some(t))),
+        "av1" => av1_layers(payload).map(|(s, t)| (Some(s), Some(t))),
+        _ => None,
+    }
+}
+
+/// RFC 7741 payload descriptor TID
+fn vp8_temporal(p: &[u8]) -> Option<u8> {
+    // X bit announces the extension byte
+    if p.first()? & 0x80 == 0 {
+        return None;
+    }
+    let x = *p.get(1)?;
+    let mut pos = 2;
+    if x & 0x80 != 0 {
+        pos += if p.get(pos)? & 0x80 != 0 { 2 } else { 1 };
+    }
+    if x & 0x40 != 0 {
+        pos += 1;
+    }
+    if x & 0x20 == 0 {
+        return None;
+    }
+    Some(p.get(pos)? >> 6)
+}
+
+/// RFC 9628 payload descriptor layer indices, (SID, TID)
+fn vp9_layers(p: &[u8]) -> Option<(u8, u8)> {
+    let b = *p.first()?;
+    if b & 0x20 == 0 {
+        return None;
+    }
+    let mut pos = 1;
+    if b & 0x80 != 0 {
+        pos += if p.get(pos)? & 0x80 != 0 { 2 } else { 1 };
+    }
+    let l = *p.get(pos)?;
+    Some(((l >> 1) & 0x07, l >> 5))
+}
+
+/// OBU extension header of the first OBU in the packet, (spatial_id, temporal_id)
+fn av1_layers(p: &[u8]) -> Option<(u8, u8)> {
+    let aggregation = *p.first()?;
+    // a continued fragment does not start with an OBU header
+    if aggregation & 0x80 != 0 {
+        return None;
+    }
+    let mut pos = 1;
+    // unless W is 1, the first element is prefixed with its leb128 length
+    if (aggregation >> 4) & 0x03 != 1 {
+        while p.get(pos)? & 0x80 != 0 {
+            pos += 1;
+        }
+        pos += 1;
+    }
+    if p.get(pos)? & 0x04 == 0 {
+        return None;
+    }
+    let ext = *p.get(pos + 1)?;
+    Some(((ext >> 3) & 0x03, ext >> 5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vp8_two_byte_picture_id() {
+        // X S, then I T, 15 bit picture id, TID 2 with Y
+        let payload = [0x90, 0xa0, 0x92, 0x34, 0xa0, 0x9d];
+        assert_eq!(vp8_temporal(&payload), Some(2));
+        assert_eq!(svc_layers(Some("vp8"), &payload), Some((None, Some(2))));
+    }
+
+    #[test]
+    fn vp8_one_byte_picture_id_and_tl0picidx() {
+        let payload = [0x80, 0xe0, 0x12, 0x05, 0x40, 0x9d];
+        assert_eq!(vp8_temporal(&payload), Some(1));
+    }
+
+    #[test]
+    fn vp8_without_tid() {
+        assert_eq!(vp8_temporal(&[0x80, 0x80, 0x12, 0x9d]), None);
+        assert_eq!(vp8_temporal(&[0x10, 0x9d]), None);
+    }
+
+    #[test]
+    fn vp9_two_byte_picture_id() {
+        // I L, 15 bit picture id, TID 3 U SID 2
+        let payload = [0xa8, 0x81, 0x23, 0x74, 0x00];
+        assert_eq!(vp9_layers(&payload), Some((2, 3)));
+        assert_eq!(svc_layers(Some("vp9"), &payload), Some((Some(2), Some(3))));
+    }
+
+    #[test]
+    fn vp9_one_byte_picture_id() {
+        assert_eq!(vp9_layers(&[0xa0, 0x23, 0x22, 0x00]), Some((1, 1)));
+        // no L bit, no layer indices
+        assert_eq!(vp9_layers(&[0x80, 0x23, 0x00]), None);
+    }
+
+    #[test]
+    fn av1_single_obu_element() {
+        // W=1: the only element has no length field, frame OBU with extension
+        let payload = [0x10, 0x34, 0x48, 0x00];
+        assert_eq!(av1_layers(&payload), Some((1, 2)));
+        assert_eq!(svc_layers(Some("av1"), &payload), Some((Some(1), Some(2))));
+    }
+
+    #[test]
+    fn av1_length_prefixed_element() {
+        // W=0: two byte leb128 length before the OBU header
+        let payload = [0x00, 0x81, 0x01, 0x34, 0x28, 0x00];
+        assert_eq!(av1_layers(&payload), Some((1, 1)));
+    }
+
+    #[test]
+    fn av1_continued_fragment_or_no_extension() {
+        assert_eq!(av1_layers(&[0x90, 0x34, 0x48]), None);
+        assert_eq!(av1_layers(&[0x10, 0x30, 0x00]), None);
+    }
+}
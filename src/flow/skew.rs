@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use super::{FlowPacket, timestamp::MediaClock};
+
+pub struct SkewStats {
+    /// sender clock rate error against capture clock, positive when sender runs fast
+    pub skew_ppm: f64,
+    pub exceeded: bool,
+}
+/// at most this many packets are sampled, Theil-Sen is quadratic
+const MAX_POINTS: usize = 400;
+/// too short streams give no useful estimate
+const MIN_DURATION: Duration = Duration::from_secs(10);
+/// slopes of close points are dominated by jitter
+const MIN_PAIR_DISTANCE_USEC: i64 = 1_000_000;
+
+/// Estimate clock skew of a stream by Theil-Sen regression of media time
+/// against arrival time.
+pub fn estimate_skew(rx_pkts: &[FlowPacket], clock_rate: u32, threshold_ppm: f64) -> Option<SkewStats> {
+    let rtp_pkts: Vec<&FlowPacket> = rx_pkts.iter().filter(|p| p.rtp().is_some()).collect();
+    let (first, last) = (rtp_pkts.first()?, rtp_pkts.last()?);
+    if last.ts.duration_since(first.ts).unwrap_or_default() < MIN_DURATION {
+        return None;
+    }
+    let mut clock = MediaClock::new(clock_rate);
+    let samples: Vec<(i64, i64)> = rtp_pkts.iter().filter_map(|p| clock.update(p)).collect();
+    // padding-only streams have no media time
+    if samples.len() < 2 {
+        return None;
+    }
+    let step = samples.len().div_ceil(MAX_POINTS);
+    let points: Vec<(i64, i64)> = samples
+        .iter()
+        .step_by(step)
+        .map(|&(arrival, media)| (arrival - samples[0].0, media))
+        .collect();
+
+    let mut slopes = Vec::with_capacity(points.len() * points.len() / 2);
+    for (i, &(x1, y1)) in points.iter().enumerate() {
+        for &(x2, y2) in points[(i + 1)..].iter() {
+            if x2 - x1 >= MIN_PAIR_DISTANCE_USEC {
+                slopes.push((y2 - y1) as f64 / (x2 - x1) as f64);
+            }
+        }
+    }
+    if slopes.is_empty() {
+        return None;
+    }
+    slopes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let slope = slopes[slopes.len() / 2];
+    let skew_ppm = (slope - 1.0) * 1e6;
+    Some(SkewStats {
+        skew_ppm,
+        exceeded: skew_ppm.abs() > threshold_ppm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    use crate::centrifuge::rtp;
+    use crate::flow::FlowPayload;
+
+    /// 8 kHz stream with 20 ms frames sent every `period` usec of capture
+    /// time, every 50th packet delayed by 30 ms
+    fn stream(pkts: u32, period: u64) -> Vec<FlowPacket> {
+        (0..pkts)
+            .map(|i| {
+                let mut data = vec![0x80, 0x00];
+                data.extend_from_slice(&(i as u16).to_be_bytes());
+                data.extend_from_slice(&(i * 160).to_be_bytes());
+                data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+                data.extend_from_slice(&[0x55; 12]);
+                let jitter = if i % 50 == 0 { 30_000 } else { 0 };
+                FlowPacket {
+                    ts: SystemTime::UNIX_EPOCH + Duration::from_micros(i as u64 * period + jitter),
+                    payload: FlowPayload::Rtp(rtp::extract(&data).unwrap()),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fast_sender() {
+        // 20 ms of media every 19.998 ms: the sender clock runs 100 ppm fast
+        let stats = estimate_skew(&stream(1000, 19_998), 8000, 50.0).unwrap();
+        assert!((stats.skew_ppm - 100.0).abs() < 1.0, "{}", stats.skew_ppm);
+        assert!(stats.exceeded);
+    }
+
+    #[test]
+    fn slow_sender_below_threshold() {
+        let stats = estimate_skew(&stream(1000, 20_001), 8000, 100.0).unwrap();
+        assert!((stats.skew_ppm + 50.0).abs() < 1.0, "{}", stats.skew_ppm);
+        assert!(!stats.exceeded);
+    }
+
+    #[test]
+    fn outliers_do_not_bias_the_estimate() {
+        let stats = estimate_skew(&stream(1000, 20_000), 8000, 100.0).unwrap();
+        assert!(stats.skew_ppm.abs() < 1.0, "{}", stats.skew_ppm);
+    }
+
+    #[test]
+    fn short_stream() {
+        assert!(estimate_skew(&stream(400, 20_000), 8000, 100.0).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::SystemTime;
+
+use super::{FlowPacket, call::{seq_delta, timestamp_delta, timestamp_to_usec}};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AnomalyKind {
+    /// timestamp moved inconsistently with wall clock
+    Jump,
+    /// timestamp went backwards while sequence went forwards
+    Backward,
+    /// timestamp advanced more than the sequence progression explains
+    SeqMismatch,
+}
+pub struct TimestampAnomaly {
+    pub kind: AnomalyKind,
+    pub ts: SystemTime,
+    pub seqnum: u16,
+    /// rtp timestamp delta from the previous packet
+    pub delta: i64,
+}
+pub struct TimestampStats {
+    pub anomalies: Vec<TimestampAnomaly>,
+    pub talk_spurts: u64,
+}
+
+impl TimestampStats {
+    pub fn count(&self, kind: AnomalyKind) -> usize {
+        self.anomalies.iter().filter(|a| a.kind == kind).count()
+    }
+}
+/// media and arrival time disagreeing by more than this is a discontinuity, usec
+const JUMP_THRESHOLD: i64 = 1_000_000;
+
+/// return true if an rtp timestamp delta can't be explained by the arrival delta
+pub fn is_jump(media_delta: i64, arrival_delta: i64) -> bool {
+    (media_delta - arrival_delta).abs() > JUMP_THRESHOLD
+}
+/// Maps rtp timestamps of a stream to continuous media time, re-anchoring at
+/// timestamp jumps so that metrics survive hold/resume.
+pub struct MediaClock {
+    clock_rate: u32,
+    last: Option<(i64, u32, i64)>,
+}
+
+impl MediaClock {
+    pub fn new(clock_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            last: None,
+        }
+    }
+    /// return (arrival, media time) of a packet, usec. Padding-only packets
+    /// reuse a stale timestamp and are left out.
+    pub fn update(&mut self, pkt: &FlowPacket) -> Option<(i64, i64)> {
+        let rtp = pkt.rtp()?;
+        if rtp.padding_only() {
+            return None;
+        }
+        let arrival = pkt.ts.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_micros() as i64;
+        let media = match self.last {
+            Some((last_arrival, last_ts, last_media)) => {
+                let md = timestamp_to_usec(timestamp_delta(rtp.header.timestamp, last_ts), self.clock_rate);
+                let ad = arrival - last_arrival;
+                if is_jump(md, ad) {
+                    last_media + ad
+                } else {
+                    last_media + md
+                }
+            }
+            None => 0,
+        };
+        self.last = Some((arrival, rtp.header.timestamp, media));
+        Some((arrival, media))
+    }
+}
+
+pub fn detect_anomalies(rx_pkts: &[FlowPacket], clock_rate: u32) -> TimestampStats {
+    let frame_ts = typical_frame_ts(rx_pkts);
+    let mut anomalies = Vec::new();
+    let mut talk_spurts = 0u64;
+    let mut last: Option<&FlowPacket> = None;
+    for p in rx_pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) if !rtp.padding_only() => rtp,
+            _ => continue,
+        };
+        if rtp.header.marker != 0 {
+            talk_spurts += 1;
+        }
+        let prev = match last {
+            Some(prev) => prev,
+            None => {
+                last = Some(p);
+                continue;
+            }
+        };
+        let prev_rtp = prev.rtp().unwrap();
+        let sn = seq_delta(rtp.header.seqnum, prev_rtp.header.seqnum);
+        if sn <= 0 {
+            // reordered or duplicate, compare against the highest packet only
+            continue;
+        }
+        let delta = timestamp_delta(rtp.header.timestamp, prev_rtp.header.timestamp);
+        let arrival_delta = p.ts.duration_since(prev.ts).unwrap_or_default().as_micros() as i64;
+        let kind = if is_jump(timestamp_to_usec(delta, clock_rate), arrival_delta) {
+            Some(AnomalyKind::Jump)
+        } else if delta < 0 {
+            Some(AnomalyKind::Backward)
+        } else if frame_ts > 0 && rtp.header.marker == 0 && delta > frame_ts * sn * 2 {
+            Some(AnomalyKind::SeqMismatch)
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            anomalies.push(TimestampAnomaly {
+                kind,
+                ts: p.ts,
+                seqnum: rtp.header.seqnum,
+                delta,
+            });
+        }
+        last = Some(p);
+    }
+    TimestampStats {
+        anomalies,
+        talk_spurts,
+    }
+}
+/// median timestamp increment between consecutive packets
+fn typical_frame_ts(rx_pkts: &[FlowPacket]) -> i64 {
+    let media: Vec<&FlowPacket> = rx_pkts
+        .iter()
+        .filter(|p| p.rtp().is_some_and(|rtp| !rtp.padding_only()))
+        .collect();
+    let mut deltas: Vec<i64> = media
+        .windows(2)
+        .filter_map(|pair| {
+            let (r1, r2) = (pair[0].rtp()?, pair[1].rtp()?);
+            if seq_delta(r2.header.seqnum, r1.header.seqnum) != 1 {
+                return None;
+            }
+            let d = timestamp_delta(r2.header.timestamp, r1.header.timestamp);
+            if d > 0 { Some(d) } else { None }
+        })
+        .collect();
+    if deltas.is_empty() {
+        return 0;
+    }
+    deltas.sort_unstable();
+    deltas[deltas.len() / 2]
+}
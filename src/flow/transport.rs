@@ -0,0 +1,255 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use crate::structs::rtcp::{RtcpPacket, TransportFeedback};
+
+use super::{FlowPacket, sdp::SdpInfo};
+
+pub const ABS_SEND_TIME: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+pub const TRANSPORT_WIDE_CC: &str = "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01";
+/// abs-send-time is 6.18 fixed point seconds in 24 bits
+const ABS_SEND_TIME_WRAP: i64 = 1 << 24;
+/// twcc receive delta unit, usec
+const TWCC_DELTA_UNIT: i64 = 250;
+
+/// Extension ids of the send time extensions, from the extmap
+#[derive(Clone, Copy)]
+pub struct TransportIds {
+    pub abs_send_time: Option<u8>,
+    pub transport_cc: Option<u8>,
+}
+
+impl TransportIds {
+    pub fn new(sdp: &SdpInfo) -> Self {
+        Self {
+            abs_send_time: sdp.extension_id(ABS_SEND_TIME),
+            transport_cc: sdp.extension_id(TRANSPORT_WIDE_CC),
+        }
+    }
+}
+
+/// One-way delay from the sender's clock to the capture point, relative to
+/// the smallest delay seen.
+pub struct DelayVariationStats {
+    pub avg: u64,
+    pub max: u64,
+    /// (arrival, delay variation usec)
+    pub samples: Vec<(SystemTime, u64)>,
+}
+
+/// TWCC feedback of the receiver lined up against the same packets seen at
+/// the capture point.
+pub struct TwccComparison {
+    pub feedback_pkts: usize,
+    pub reported_received: usize,
+    pub reported_lost: usize,
+    /// reported lost although they passed the capture point
+    pub lost_after_capture: usize,
+    /// |reported - capture point inter-packet delta|, usec
+    pub delta_diff_avg: u64,
+    pub delta_diff_max: u64,
+    /// (feedback arrival, reported delta usec, capture point delta usec)
+    pub samples: Vec<(SystemTime, i64, i64)>,
+}
+
+pub fn delay_variation(pkts: &[FlowPacket], ids: TransportIds) -> Option<DelayVariationStats> {
+    let id = ids.abs_send_time?;
+    let mut delays: Vec<(SystemTime, i64)> = Vec::new();
+    let mut last: Option<(i64, i64)> = None;
+    for p in pkts.iter() {
+        let data = match p.rtp().and_then(|rtp| rtp.header.extension(id)) {
+            Some(data) if data.len() >= 3 => data,
+            _ => continue,
+        };
+        let raw = (data[0] as i64) << 16 | (data[1] as i64) << 8 | data[2] as i64;
+        // unwrap around the 64 second period
+        let send = match last {
+            Some((last_raw, last_send)) => {
+                let mut delta = (raw - last_raw).rem_euclid(ABS_SEND_TIME_WRAP);
+                if delta >= ABS_SEND_TIME_WRAP / 2 {
+                    delta -= ABS_SEND_TIME_WRAP;
+                }
+                last_send + delta
+            }
+            None => raw,
+        };
+        last = Some((raw, send));
+        let send_usec = (send * 1_000_000) >> 18;
+        let arrival = p.ts.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_micros() as i64;
+        delays.push((p.ts, arrival - send_usec));
+    }
+    let min = delays.iter().map(|d| d.1).min()?;
+    let samples: Vec<(SystemTime, u64)> = delays.into_iter().map(|(ts, d)| (ts, (d - min) as u64)).collect();
+    Some(DelayVariationStats {
+        avg: samples.iter().map(|s| s.1).sum::<u64>() / samples.len() as u64,
+        max: samples.iter().map(|s| s.1).max().unwrap(),
+        samples,
+    })
+}
+
+/// Rebuild the inter-packet deltas a TWCC receiver at the capture point would
+/// report for `pkts`, and compare them with the feedback in `feedback_pkts`
+/// sent by the receiver of `pkts`.
+pub fn compare(pkts: &[FlowPacket], feedback_pkts: &[FlowPacket], ids: TransportIds) -> Option<TwccComparison> {
+    ids.transport_cc?;
+    let mut captured: HashMap<u16, SystemTime> = HashMap::new();
+    let mut cursor = 0;
+    let mut cmp = TwccComparison {
+        feedback_pkts: 0,
+        reported_received: 0,
+        reported_lost: 0,
+        lost_after_capture: 0,
+        delta_diff_avg: 0,
+        delta_diff_max: 0,
+        samples: vec![],
+    };
+    for p in feedback_pkts.iter() {
+        let rtcp = match p.rtcp() {
+            Some(rtcp) => rtcp,
+            None => continue,
+        };
+        // packets seen before the feedback, the latest one for a wrapped seq
+        while cursor < pkts.len() && pkts[cursor].ts <= p.ts {
+            if let Some(seq) = twcc_seq(&pkts[cursor], ids) {
+                captured.insert(seq, pkts[cursor].ts);
+            }
+            cursor += 1;
+        }
+        for packet in rtcp.packets.iter() {
+            let twcc = match packet {
+                RtcpPacket::TransportFeedback(TransportFeedback::TransportCc(twcc)) => twcc,
+                _ => continue,
+            };
+            cmp.feedback_pkts += 1;
+            let mut last_captured: Option<SystemTime> = None;
+            for status in twcc.packets.iter() {
+                let ts = captured.get(&status.seq).copied();
+                let delta = match status.delta {
+                    Some(delta) => delta as i64 * TWCC_DELTA_UNIT,
+                    None => {
+                        cmp.reported_lost += 1;
+                        if ts.is_some() {
+                            cmp.lost_after_capture += 1;
+                        }
+                        continue;
+                    }
+                };
+                cmp.reported_received += 1;
+                // the first delta is relative to the reference time, the
+                // others to the previous received packet
+                if let (Some(ts), Some(last)) = (ts, last_captured) {
+                    let observed = ts.duration_since(last).unwrap_or_default().as_micros() as i64;
+                    cmp.samples.push((p.ts, delta, observed));
+                }
+                last_captured = ts;
+            }
+        }
+    }
+    if cmp.feedback_pkts == 0 {
+        return None;
+    }
+    let diffs: Vec<u64> = cmp.samples.iter().map(|s| (s.1 - s.2).unsigned_abs()).collect();
+    if !diffs.is_empty() {
+        cmp.delta_diff_avg = diffs.iter().sum::<u64>() / diffs.len() as u64;
+        cmp.delta_diff_max = *diffs.iter().max().unwrap();
+    }
+    Some(cmp)
+}
+
+pub fn twcc_seq(pkt: &FlowPacket, ids: TransportIds) -> Option<u16> {
+    let data = pkt.rtp()?.header.extension(ids.transport_cc?)?;
+    if data.len() < 2 {
+        return None;
+    }
+    Some((data[0] as u16) << 8 | data[1] as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::centrifuge::{rtcp, rtp};
+    use crate::flow::FlowPayload;
+
+    const IDS: TransportIds = TransportIds {
+        abs_send_time: Some(3),
+        transport_cc: Some(5),
+    };
+
+    fn at(usec: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1000) + Duration::from_micros(usec)
+    }
+
+    /// rtp with abs-send-time (id 3) and transport-cc (id 5) extensions
+    fn rtp_pkt(ts: SystemTime, send: u32, twcc_seq: u16) -> FlowPacket {
+        let mut data = vec![0x90, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        data.extend_from_slice(&[0xbe, 0xde, 0x00, 0x02, 0x32]);
+        data.extend_from_slice(&send.to_be_bytes()[1..]);
+        data.push(0x51);
+        data.extend_from_slice(&twcc_seq.to_be_bytes());
+        data.push(0x00);
+        data.extend_from_slice(&[0x55; 12]);
+        FlowPacket {
+            ts,
+            payload: FlowPayload::Rtp(rtp::extract(&data).unwrap()),
+        }
+    }
+
+    #[test]
+    fn delay_variation_across_abs_send_time_wrap() {
+        // 1/64 sec apart, the third packet wraps the 24 bit send time
+        let extra = [0, 5_000, 0, 20_000];
+        let pkts: Vec<FlowPacket> = extra
+            .iter()
+            .enumerate()
+            .map(|(k, extra)| {
+                let send = ((1 << 24) - 8192 + k as u32 * 4096) % (1 << 24);
+                rtp_pkt(at(k as u64 * 15_625 + extra), send, k as u16)
+            })
+            .collect();
+        let stats = delay_variation(&pkts, IDS).unwrap();
+        let samples: Vec<u64> = stats.samples.iter().map(|s| s.1).collect();
+        assert_eq!(samples, vec![0, 5_000, 0, 20_000]);
+        assert_eq!((stats.avg, stats.max), (6_250, 20_000));
+    }
+
+    #[test]
+    fn twcc_feedback_against_capture() {
+        // seq 14 never reaches the capture point
+        let pkts: Vec<FlowPacket> = [0, 1_000, 3_000, 6_000]
+            .iter()
+            .enumerate()
+            .map(|(i, ts)| rtp_pkt(at(*ts), 0, 10 + i as u16))
+            .collect();
+        // 10, 11 and 12 received with deltas of 2, 1 and 2.5 ms, 13 and 14 lost
+        let feedback = [
+            0x8f, 205, 0x00, 0x06,
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x0a, 0x00, 0x05, 0x00, 0x00, 0x01, 0x00,
+            0xd5, 0x00, 0x08, 0x04, 0x0a, 0x00, 0x00, 0x00,
+        ];
+        let feedback = FlowPacket {
+            ts: at(50_000),
+            payload: FlowPayload::Rtcp(rtcp::extract(&feedback).unwrap()),
+        };
+        let cmp = compare(&pkts, &[feedback], IDS).unwrap();
+        assert_eq!(cmp.feedback_pkts, 1);
+        assert_eq!((cmp.reported_received, cmp.reported_lost), (3, 2));
+        assert_eq!(cmp.lost_after_capture, 1);
+        let deltas: Vec<(i64, i64)> = cmp.samples.iter().map(|s| (s.1, s.2)).collect();
+        assert_eq!(deltas, vec![(1_000, 1_000), (2_500, 2_000)]);
+        assert_eq!((cmp.delta_diff_avg, cmp.delta_diff_max), (250, 500));
+    }
+
+    #[test]
+    fn no_extension_ids() {
+        let pkts = [rtp_pkt(at(0), 0, 0)];
+        let ids = TransportIds {
+            abs_send_time: None,
+            transport_cc: None,
+        };
+        assert!(delay_variation(&pkts, ids).is_none());
+        assert!(compare(&pkts, &[], ids).is_none());
+        assert_eq!(twcc_seq(&pkts[0], IDS), Some(0));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashSet, time::{Duration, SystemTime}};
+
+use super::{FlowPacket, call::{match_delays, seq_delta, InterarrivalJitter}};
+
+pub struct WindowStats {
+    pub start: SystemTime,
+    pub pkts: u64,
+    pub lost_pkts: u64,
+    pub lost_rate: f64,
+    /// interarrival jitter at window end, usec
+    pub jitter: u64,
+    /// payload bits per second
+    pub bitrate: u64,
+    pub pkt_rate: f64,
+    /// forwarding delay, usec
+    pub delay_avg: u64,
+    pub delay_max: u64,
+}
+
+impl WindowStats {
+    fn new(start: SystemTime) -> Self {
+        Self {
+            start,
+            pkts: 0,
+            lost_pkts: 0,
+            lost_rate: 0.0,
+            jitter: 0,
+            bitrate: 0,
+            pkt_rate: 0.0,
+            delay_avg: 0,
+            delay_max: 0,
+        }
+    }
+}
+
+/// Split a received stream into fixed-interval windows starting at its first packet.
+pub fn compute_window_stats(
+    rx_pkts: &[FlowPacket],
+    tx_pkts: &[FlowPacket],
+    clock_rate: u32,
+    interval: Duration,
+) -> Vec<WindowStats> {
+    let start = match rx_pkts.first() {
+        Some(p) => p.ts,
+        None => return vec![],
+    };
+    let index = |ts: SystemTime| {
+        (ts.duration_since(start).unwrap_or_default().as_micros() / interval.as_micros()) as usize
+    };
+    let count = index(rx_pkts.last().unwrap().ts) + 1;
+    let mut windows: Vec<WindowStats> = (0..count)
+        .map(|i| WindowStats::new(start + interval * i as u32))
+        .collect();
+
+    let mut jitter = InterarrivalJitter::new(clock_rate);
+    let mut bytes = vec![0u64; count];
+    let mut highest = vec![None; count];
+    let mut seen = HashSet::with_capacity(rx_pkts.len());
+    let mut highest_seq = 0i64;
+    let mut highest_raw_seq = None;
+    for p in rx_pkts.iter() {
+        let rtp = match p.rtp() {
+            Some(rtp) => rtp,
+            None => continue,
+        };
+        let seq = match highest_raw_seq {
+            Some(raw) => highest_seq + seq_delta(rtp.header.seqnum, raw),
+            None => 0,
+        };
+        if highest_raw_seq.is_none() || seq > highest_seq {
+            highest_seq = seq;
+            highest_raw_seq = Some(rtp.header.seqnum);
+        }
+        let i = index(p.ts);
+        let w = &mut windows[i];
+        w.jitter = jitter.update(p);
+        if !seen.insert(seq) {
+            continue;
+        }
+        w.pkts += 1;
+        bytes[i] += rtp.payload.len() as u64;
+        highest[i] = Some(highest_seq);
+    }
+
+    // expected packets of a window are counted from the previous window's highest seq
+    let mut last_highest = -1i64;
+    for (i, w) in windows.iter_mut().enumerate() {
+        if let Some(h) = highest[i] {
+            let expected = (h - last_highest).max(0) as u64;
+            w.lost_pkts = expected.saturating_sub(w.pkts);
+            if expected > 0 {
+                w.lost_rate = w.lost_pkts as f64 / expected as f64 * 100.0;
+            }
+            last_highest = h;
+        }
+        let secs = interval.as_secs_f64();
+        w.bitrate = (bytes[i] as f64 * 8.0 / secs) as u64;
+        w.pkt_rate = w.pkts as f64 / secs;
+    }
+
+    let mut delay_total = vec![0u64; count];
+    let mut delay_count = vec![0u64; count];
+    for (ts, d) in match_delays(rx_pkts, tx_pkts) {
+        let i = index(ts);
+        delay_total[i] += d;
+        delay_count[i] += 1;
+        if windows[i].delay_max < d {
+            windows[i].delay_max = d;
+        }
+    }
+    for (i, w) in windows.iter_mut().enumerate() {
+        if let Some(avg) = delay_total[i].checked_div(delay_count[i]) {
+            w.delay_avg = avg;
+        }
+    }
+    windows
+}
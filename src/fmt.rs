@@ -360,8 +360,19 @@ impl Format {
     fn print_debugging_udp(&self, udp: udp::UDP) -> String {
         use crate::structs::udp::UDP::*;
         match udp {
-            Rtp(rtp) => self.colorify(Blue, format!("remaining: {:?}", rtp)),
-            Rtcp(rtcp) => self.colorify(Blue, format!("remaining: {:?}", rtcp)),
+            Rtp(rtp) => {
+                let lines: Vec<String> = rtp
+                    .header
+                    .extensions
+                    .iter()
+                    .map(|e| format!("ext id={} data={:?}", e.id, e.data))
+                    .collect();
+                self.colorify(Blue, format!("remaining: {:?}\n\t\t\t{}", rtp, lines.join("\n\t\t\t")))
+            }
+            Rtcp(rtcp) => {
+                let lines: Vec<String> = rtcp.packets.iter().map(|p| format!("{:?}", p)).collect();
+                self.colorify(Blue, format!("rtcp: {:?}\n\t\t\t{}", rtcp.header, lines.join("\n\t\t\t")))
+            }
             Text(text) => self.colorify(Blue, format!("remaining: {:?}", text)),
             Binary(x) => self.colorify(Yellow, format!("remaining: {:?}", x)),
         }
@@ -36,6 +36,7 @@ fn main() -> Result<()> {
     };
 
     let colors = atty::is(atty::Stream::Stdout);
+    let mut sdp = flow::sdp::SdpInfo::new(&args.extmap, &args.rtpmap);
     let config = fmt::Config::new(layout, args.verbose, colors);
 
     let cap = if !args.read {
@@ -78,6 +79,7 @@ fn main() -> Result<()> {
         let datalink = datalink.clone();
         let filter = filter.clone();
         let tx = tx.clone();
+        let ports = args.port_heuristic;
         thread::spawn(move || loop {
             let packet = {
                 let mut cap = cap.lock().unwrap();
@@ -87,7 +89,7 @@ fn main() -> Result<()> {
             if let Ok(Some(packet)) = packet {
                 let ts = SystemTime::UNIX_EPOCH
                     + Duration::new(packet.ts.tv_sec as _, (packet.ts.tv_usec * 1000) as _);
-                let packet = centrifuge::parse(&datalink, &packet.data);
+                let packet = centrifuge::parse(&datalink, &packet.data, ports);
                 if filter.matches(&packet) {
                     tx.send((ts, packet)).unwrap()
                 }
@@ -99,13 +101,23 @@ fn main() -> Result<()> {
     }
     drop(tx);
 
+    let jb_config = flow::jitter_buffer::JitterBufferConfig {
+        mode: args.jb_mode,
+        size: args.jb_size * 1000,
+        min_delay: args.jb_min_delay * 1000,
+        max_delay: args.jb_max_delay * 1000,
+    };
+
     let mut conn_map = flow::connection::ConnectionMap::new();
     let local_ip = "192.168.6.51".parse().unwrap();
     for (ts, packet) in rx.iter() {
         if let Some(data) = flow::extract_flow_data(&local_ip, ts, &packet) {
+            sdp.learn(&data);
+            conn_map.mid_id = sdp.extension_id(flow::simulcast::MID);
             conn_map.add(data);
         }
     }
+    conn_map.resolve_clock_rates(&sdp);
     println!("{:-^100}", " connections ");
     let mut conn_list = vec![];
     for list in conn_map.map.values() {
@@ -145,13 +157,38 @@ fn main() -> Result<()> {
             conn.ingress_ssrc().unwrap(),
             conn.egress_ssrc().unwrap(),
         );
+        for e in conn.ingress_congestion().iter() {
+            let kind = match e.kind {
+                flow::congestion::EpisodeKind::BuildUp => "build-up",
+                flow::congestion::EpisodeKind::Release => "release",
+            };
+            println!(
+                "     congestion {:8} {} ~ {} {:>6} msec",
+                kind,
+                DateTime::<Local>::from(e.start).format("%H:%M:%S.%3f"),
+                DateTime::<Local>::from(e.end).format("%H:%M:%S.%3f"),
+                e.magnitude / 1000
+            );
+        }
     }
 
     println!("{:-^100}", " calls ");
     let calls = flow::call::extract_calls(&conn_list);
+    let level_ids = flow::audio_level::LevelIds::new(&sdp);
+    let transport_ids = flow::transport::TransportIds::new(&sdp);
     for c in calls.iter() {
         let stats = c.compute_stats();
-        
+        let jb_stats = c.emulate_jitter_buffer(&jb_config);
+        let skew_stats = c.estimate_skew(args.skew_threshold);
+        let ts_stats = c.detect_timestamp_anomalies();
+        let rtt_stats = c.compute_rtt();
+        let reception_stats = c.compare_reception_reports();
+        let voip_metrics = c.endpoint_voip_metrics();
+        let feedback_stats = c.collect_feedback();
+        let level_stats = c.track_audio_levels(level_ids);
+        let transport_stats = c.analyze_transport(transport_ids);
+        let window_stats = args.interval.map(|secs| c.compute_window_stats(Duration::from_secs(secs.get())));
+
         let (ia, ib) = c.peer1.ingress_tsrange().unwrap();
         let dia = DateTime::<Local>::from(ia);
         let dib = DateTime::<Local>::from(ib);
@@ -214,6 +251,741 @@ fn main() -> Result<()> {
             stats.peer2_delay.max,
             stats.peer2_delay.std,
         );
+        print_rtt_stats("forward", &rtt_stats.peer1, args.verbose > 0);
+        print_rtt_stats("backward", &rtt_stats.peer2, args.verbose > 0);
+        print_reception_stats("peer1", &reception_stats.peer1);
+        print_reception_stats("peer2", &reception_stats.peer2);
+        print_voip_metrics("peer1", &voip_metrics.peer1);
+        print_voip_metrics("peer2", &voip_metrics.peer2);
+        print_feedback_stats("from peer1", &feedback_stats.from_peer1, args.verbose > 0);
+        print_feedback_stats("to   peer1", &feedback_stats.to_peer1, args.verbose > 0);
+        print_feedback_stats("from peer2", &feedback_stats.from_peer2, args.verbose > 0);
+        print_feedback_stats("to   peer2", &feedback_stats.to_peer2, args.verbose > 0);
+
+        println!(
+            "{} jitter_buffer   forward: lost / late / effective_lost / avg_delay_msec / max_delay_msec",
+            " ".repeat(30)
+        );
+        println!(
+            "{}                  {:4} / {:4} /       {:7.4}% / {:14} / {:14}",
+            " ".repeat(30),
+            jb_stats.peer1_ingress.lost_pkts,
+            jb_stats.peer1_ingress.late_pkts,
+            jb_stats.peer1_ingress.effective_lost_rate,
+            jb_stats.peer1_ingress.avg_delay / 1000,
+            jb_stats.peer1_ingress.max_delay / 1000
+        );
+        println!(
+            "{} jitter_buffer  backward: lost / late / effective_lost / avg_delay_msec / max_delay_msec",
+            " ".repeat(30)
+        );
+        println!(
+            "{}                  {:4} / {:4} /       {:7.4}% / {:14} / {:14}",
+            " ".repeat(30),
+            jb_stats.peer2_ingress.lost_pkts,
+            jb_stats.peer2_ingress.late_pkts,
+            jb_stats.peer2_ingress.effective_lost_rate,
+            jb_stats.peer2_ingress.avg_delay / 1000,
+            jb_stats.peer2_ingress.max_delay / 1000
+        );
+
+        println!(
+            "{} clock_skew_ppm  forward: {}",
+            " ".repeat(30),
+            display_skew(&skew_stats.peer1_ingress)
+        );
+        println!(
+            "{} clock_skew_ppm backward: {}",
+            " ".repeat(30),
+            display_skew(&skew_stats.peer2_ingress)
+        );
+
+        print_delay_variation_stats("forward", &transport_stats.peer1_ingress, args.verbose > 0);
+        print_delay_variation_stats("backward", &transport_stats.peer2_ingress, args.verbose > 0);
+        print_twcc_comparison("to   peer1", &transport_stats.to_peer1, args.verbose > 0);
+        print_twcc_comparison("from peer1", &transport_stats.from_peer1, args.verbose > 0);
+        print_twcc_comparison("to   peer2", &transport_stats.to_peer2, args.verbose > 0);
+        print_twcc_comparison("from peer2", &transport_stats.from_peer2, args.verbose > 0);
+
+        print_audio_level_stats("forward", &level_stats.peer1_ingress);
+        print_audio_level_stats("backward", &level_stats.peer2_ingress);
+        if args.verbose > 0 {
+            for (ts, speaker) in level_stats.speakers.iter() {
+                println!(
+                    "{}     {} {:?}",
+                    " ".repeat(30),
+                    DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                    speaker
+                );
+            }
+        }
+
+        print_timestamp_stats("forward", &ts_stats.peer1_ingress, args.verbose > 0);
+        print_timestamp_stats("backward", &ts_stats.peer2_ingress, args.verbose > 0);
+
+        if let Some(window_stats) = window_stats {
+            print_window_stats("forward", &window_stats.peer1_ingress);
+            print_window_stats("backward", &window_stats.peer2_ingress);
+        }
+    }
+
+    println!("{:-^100}", " bundle ");
+    for b in flow::bundle::analyze(&conn_map, &sdp).iter() {
+        println!("     {} <-> {} streams={}", b.local, b.remote, b.streams.len());
+        for s in b.streams.iter() {
+            println!(
+                "{}ssrc= 0x{:08X} {:7?} mid={:4} pt={:>3} {:8} pkts={:>6} bytes={:>10} lost={:>5} ({:.2}%) jitter={}",
+                " ".repeat(10),
+                s.ssrc,
+                s.dir,
+                s.mid.as_deref().unwrap_or("-"),
+                s.pt,
+                s.codec.as_deref().unwrap_or("-"),
+                s.pkts,
+                s.bytes,
+                s.lost,
+                s.loss_rate,
+                s.jitter
+            );
+        }
+    }
+
+    println!("{:-^100}", " rtcp ");
+    for c in flow::conformance::check(&conn_list, Duration::from_secs(args.rtcp_timeout)).iter() {
+        let msec = |i: Option<u64>| match i {
+            Some(i) => format!("{}", i / 1000),
+            None => "-".to_string(),
+        };
+        println!(
+            "     ssrc= 0x{:08X} {:7?} {:20} pkts={:>5} interval_msec min / max / avg {:>5} / {:>5} / {:>5} share={:.2}%{}",
+            c.ssrc,
+            c.dir,
+            c.remote,
+            c.rtcp_pkts,
+            msec(c.interval_min),
+            msec(c.interval_max),
+            msec(c.interval_avg),
+            c.rtcp_share,
+            if c.flooding() { " (flooding)" } else { "" }
+        );
+        println!(
+            "{}bytes rtp / rtcp {:>10} / {:<8}",
+            " ".repeat(10),
+            c.rtp_bytes,
+            c.rtcp_bytes
+        );
+        if c.missing_sr {
+            println!("{}no sender report for the stream", " ".repeat(10));
+        }
+        if c.missing_rr {
+            println!("{}no receiver report about the stream", " ".repeat(10));
+        }
+        for (ts, len) in c.silent_periods.iter() {
+            println!(
+                "{}no rtcp for {} seconds since {}",
+                " ".repeat(10),
+                len / 1_000_000,
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f")
+            );
+        }
+    }
+
+    println!("{:-^100}", " streams ");
+    for s in flow::lifecycle::track(&conn_list, Duration::from_secs(args.idle_timeout)).iter() {
+        let interval = |i: Option<u64>| match i {
+            Some(i) => format!("{}", i / 1000),
+            None => "-".to_string(),
+        };
+        println!(
+            "     ssrc= 0x{:08X} {:7?} {:20} {} ~ {} sr={:>4} every {:>5} msec rr={:>4} every {:>5} msec",
+            s.ssrc,
+            s.dir,
+            s.remote,
+            DateTime::<Local>::from(s.first).format("%H:%M:%S.%3f"),
+            DateTime::<Local>::from(s.last).format("%H:%M:%S.%3f"),
+            s.sr_count,
+            interval(s.sr_interval),
+            s.rr_count,
+            interval(s.rr_interval)
+        );
+        let end = match (s.end, &s.bye) {
+            (flow::lifecycle::StreamEnd::Bye, Some((ts, reason))) => format!(
+                "bye at {} reason={:?}",
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                reason.as_deref().unwrap_or("")
+            ),
+            (flow::lifecycle::StreamEnd::Timeout, _) => "timeout".to_string(),
+            _ => "capture end".to_string(),
+        };
+        println!("{}ended by {}", " ".repeat(10), end);
+        if s.pkts_after_bye > 0 {
+            println!("{}{} packets sent after bye", " ".repeat(10), s.pkts_after_bye);
+        }
+    }
+
+    println!("{:-^100}", " participants ");
+    for p in flow::participant::extract_participants(&conn_list).iter() {
+        println!(
+            "     cname={} name={} tool={}",
+            p.cname,
+            p.name.as_deref().unwrap_or("-"),
+            p.tool.as_deref().unwrap_or("-")
+        );
+        for s in p.streams.iter() {
+            println!(
+                "{}ssrc= 0x{:08X} {:7?} {:20} pkts={:>6} pt={:?}",
+                " ".repeat(10),
+                s.ssrc,
+                s.dir,
+                s.remote,
+                s.pkts,
+                s.payload_types
+            );
+        }
+    }
+
+    println!("{:-^100}", " bitrate ");
+    let (stream_bitrates, participant_bitrates) = flow::bitrate::compute(&conn_list, transport_ids);
+    for p in participant_bitrates.iter() {
+        println!("     cname={}", p.cname);
+        print_bitrate_windows(&p.windows, args.verbose > 0);
+    }
+    for s in stream_bitrates.iter() {
+        println!("     ssrc= 0x{:08X} {:7?} {:20}", s.ssrc, s.dir, s.remote);
+        print_bitrate_windows(&s.windows, args.verbose > 0);
+    }
+
+    println!("{:-^100}", " retransmission ");
+    let all_conns: Vec<&flow::connection::Connection> = conn_map.map.values().flatten().collect();
+    for r in flow::rtx::analyze(&all_conns, &sdp).iter() {
+        let msec = |i: Option<u64>| match i {
+            Some(i) => format!("{}", i / 1000),
+            None => "-".to_string(),
+        };
+        println!(
+            "     ssrc= 0x{:08X} rtx= 0x{:08X} pt={:<3} {:7?} {:20} by {:?}",
+            r.primary_ssrc,
+            r.rtx_ssrc,
+            r.rtx_pt,
+            r.dir,
+            r.remote,
+            r.association
+        );
+        println!(
+            "{}rtx / requested / retransmitted / lost / recovered / residual {:>6} / {:>6} / {:>6} / {:>6} / {:>6} / {:>6} ({:.4}%)",
+            " ".repeat(10),
+            r.rtx_pkts,
+            r.requested,
+            r.retransmitted,
+            r.lost,
+            r.recovered,
+            r.residual_lost,
+            r.residual_lost_rate
+        );
+        println!(
+            "{}latency_msec avg / max {:>5} / {:<5}",
+            " ".repeat(10),
+            msec(r.latency_avg),
+            msec(r.latency_max)
+        );
+    }
+
+    println!("{:-^100}", " padding ");
+    for s in flow::padding::analyze(&all_conns).iter() {
+        println!(
+            "     ssrc= 0x{:08X} {:7?} {:20} media={:>6} padding={:>6} bytes={:>8} filler={:>5} probes={}",
+            s.ssrc,
+            s.dir,
+            s.remote,
+            s.media_pkts,
+            s.padding_pkts,
+            s.padding_bytes,
+            s.filler_pkts,
+            s.clusters.len()
+        );
+        if args.verbose > 0 {
+            for c in s.clusters.iter() {
+                println!(
+                    "{}probe {} ~ {} pkts={:>4} bytes={:>7} kbps={}",
+                    " ".repeat(10),
+                    DateTime::<Local>::from(c.start).format("%H:%M:%S%.3f"),
+                    DateTime::<Local>::from(c.end).format("%H:%M:%S%.3f"),
+                    c.pkts,
+                    c.bytes,
+                    c.bitrate / 1000
+                );
+            }
+        }
+    }
+
+    println!("{:-^100}", " fec ");
+    for f in flow::fec::analyze(&all_conns, &sdp).iter() {
+        let mut schemes = vec![];
+        if f.red {
+            schemes.push("red".to_string());
+        }
+        if f.ulpfec {
+            schemes.push("ulpfec".to_string());
+        }
+        if let Some(ssrc) = f.flexfec_ssrc {
+            schemes.push(format!("flexfec 0x{:08X}", ssrc));
+        }
+        println!(
+            "     ssrc= 0x{:08X} {:7?} {:20} {} media / fec pkts {:>6} / {:<6} overhead={:.1}%",
+            f.ssrc,
+            f.dir,
+            f.remote,
+            schemes.join("+"),
+            f.media_pkts,
+            f.fec_pkts,
+            f.overhead()
+        );
+        println!(
+            "{}lost pre-fec / by red / by fec / post-fec {:>6} ({:.4}%) / {:>6} / {:>6} / {:>6} ({:.4}%)",
+            " ".repeat(10),
+            f.pre_fec_lost,
+            f.pre_fec_lost_rate,
+            f.recovered_by_red,
+            f.recovered_by_fec,
+            f.post_fec_lost,
+            f.post_fec_lost_rate
+        );
+    }
+
+    println!("{:-^100}", " simulcast ");
+    let (sources, subscribers) = flow::simulcast::analyze(&all_conns, &sdp);
+    for s in sources.iter() {
+        println!(
+            "     ssrc= 0x{:08X} {:20} mid={} encoding={} pkts={:>6}",
+            s.ssrc,
+            s.remote,
+            s.mid.as_deref().unwrap_or("-"),
+            s.encoding,
+            s.pkts
+        );
+    }
+    for s in subscribers.iter() {
+        let ssrcs: Vec<String> = s.ssrcs.iter().map(|ssrc| format!("0x{:08X}", ssrc)).collect();
+        println!("     subscriber {:20} ssrc= {} switches={}", s.remote, ssrcs.join(","), s.switches.len().saturating_sub(1));
+        for (ts, layer) in s.switches.iter() {
+            let id = |i: Option<u8>| match i {
+                Some(i) => format!("{}", i),
+                None => "-".to_string(),
+            };
+            println!(
+                "{}{} encoding={} spatial={} temporal={}",
+                " ".repeat(10),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                layer.encoding.as_deref().unwrap_or("-"),
+                id(layer.spatial),
+                id(layer.temporal)
+            );
+        }
+    }
+
+    println!("{:-^100}", " mixers ");
+    for m in flow::mixer::analyze(&conn_list).iter() {
+        println!(
+            "     ssrc= 0x{:08X} {:7?} {:20} pkts={:>6} mixed={:>6} contributors={}",
+            m.ssrc,
+            m.dir,
+            m.remote,
+            m.pkts,
+            m.mixed_pkts,
+            m.contributors.len()
+        );
+        for c in m.contributors.iter() {
+            let (first, last) = (c.appearances.first().unwrap().0, c.appearances.last().unwrap().1);
+            println!(
+                "{}csrc= 0x{:08X} pkts={:>6} {} ~ {} appearances={:>3} present_sec={:>6.1} active_sec={:>6.1}",
+                " ".repeat(10),
+                c.csrc,
+                c.pkts,
+                DateTime::<Local>::from(first).format("%H:%M:%S.%3f"),
+                DateTime::<Local>::from(last).format("%H:%M:%S.%3f"),
+                c.appearances.len(),
+                c.present as f64 / 1e6,
+                c.active as f64 / 1e6
+            );
+        }
+        if args.verbose > 0 {
+            for (ts, csrc) in m.speakers.iter() {
+                println!(
+                    "{}     {} speaker 0x{:08X}",
+                    " ".repeat(30),
+                    DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                    csrc
+                );
+            }
+        }
+    }
+
+    println!("{:-^100}", " lip sync ");
+    for sync in flow::lipsync::measure(&conn_list).iter() {
+        let flag = match sync.level {
+            flow::lipsync::SyncLevel::Undetectable => "",
+            flow::lipsync::SyncLevel::Detectable => " (detectable)",
+            flow::lipsync::SyncLevel::Unacceptable => " (unacceptable)",
+        };
+        println!(
+            "     {} {:?} {} audio 0x{:08X} video 0x{:08X}: max_lead_msec / max_lag_msec {:5} / {:5}{}",
+            sync.cname,
+            sync.dir,
+            sync.remote,
+            sync.audio_ssrc,
+            sync.video_ssrc,
+            sync.max_lead / 1000,
+            sync.max_lag / 1000,
+            flag
+        );
+        if args.verbose > 0 {
+            for (ts, offset) in sync.samples.iter() {
+                println!(
+                    "{}     {} {:+}",
+                    " ".repeat(30),
+                    DateTime::<Local>::from(*ts).format("%H:%M:%S"),
+                    offset / 1000
+                );
+            }
+        }
     }
     Ok(())
 }
+
+fn display_skew(skew: &Option<flow::skew::SkewStats>) -> String {
+    match skew {
+        Some(skew) if skew.exceeded => format!("{:+.1} (drifting)", skew.skew_ppm),
+        Some(skew) => format!("{:+.1}", skew.skew_ppm),
+        None => "n/a".to_string(),
+    }
+}
+
+fn print_rtt_stats(dir: &str, stats: &Option<flow::rtt::RttStats>, verbose: bool) {
+    println!(
+        "{} rtt_usec  {:>8}: min /  max /  avg",
+        " ".repeat(30),
+        dir
+    );
+    let stats = match stats {
+        Some(stats) => stats,
+        None => {
+            println!("{}                     n/a", " ".repeat(30));
+            return;
+        }
+    };
+    println!(
+        "{}                    {:4} / {:4} / {:4}",
+        " ".repeat(30),
+        stats.min,
+        stats.max,
+        stats.avg
+    );
+    if verbose {
+        for (ts, rtt) in stats.samples.iter() {
+            println!(
+                "{}     {} {}",
+                " ".repeat(30),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                rtt
+            );
+        }
+    }
+}
+
+fn print_reception_stats(peer: &str, stats: &Option<flow::reception::ReceptionComparison>) {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    println!(
+        "{} rr {}  ssrc=0x{:08X} reports={}: lost reported / egress / upstream, jitter_usec reported / egress / upstream",
+        " ".repeat(30),
+        peer,
+        stats.ssrc,
+        stats.reports
+    );
+    println!(
+        "{}                    {:7.4}% / {:7.4}% / {:7.4}%, {:6} / {:6} / {:6}",
+        " ".repeat(30),
+        stats.reported_lost_rate,
+        stats.egress_lost_rate,
+        stats.upstream_lost_rate,
+        stats.reported_jitter,
+        stats.egress_jitter,
+        stats.upstream_jitter
+    );
+    if stats.downstream_lost() > 0 {
+        println!(
+            "{}                    {} packets lost beyond capture point",
+            " ".repeat(30),
+            stats.downstream_lost()
+        );
+    }
+    if stats.upstream_lost > 0 {
+        println!(
+            "{}                    {} packets lost before capture point",
+            " ".repeat(30),
+            stats.upstream_lost
+        );
+    }
+}
+
+fn print_voip_metrics(peer: &str, metrics: &Option<structs::rtcp::VoipMetrics>) {
+    let m = match metrics {
+        Some(m) => m,
+        None => return,
+    };
+    let score = |v: u8| {
+        if v == 127 {
+            "n/a".to_string()
+        } else {
+            format!("{:.1}", v as f64 / 10.0)
+        }
+    };
+    println!(
+        "{} xr {}  ssrc=0x{:08X}: mos_lq / mos_cq / r_factor / lost / discard / rtd_msec / jb_nominal / jb_max",
+        " ".repeat(30),
+        peer,
+        m.ssrc
+    );
+    println!(
+        "{}                    {:>6} / {:>6} / {:>8} / {:7.4}% / {:7.4}% / {:8} / {:10} / {:6}",
+        " ".repeat(30),
+        score(m.mos_lq),
+        score(m.mos_cq),
+        if m.r_factor == 127 { "n/a".to_string() } else { m.r_factor.to_string() },
+        m.loss_rate as f64 / 256.0 * 100.0,
+        m.discard_rate as f64 / 256.0 * 100.0,
+        m.round_trip_delay,
+        m.jb_nominal,
+        m.jb_maximum
+    );
+}
+
+fn print_feedback_stats(dir: &str, stats: &flow::feedback::FeedbackStats, verbose: bool) {
+    if stats.nack_pkts + stats.pli_pkts + stats.fir_pkts + stats.remb_pkts + stats.twcc_pkts == 0 {
+        return;
+    }
+    println!(
+        "{} feedback {}: nack / nack_seqs / pli / fir / remb / twcc",
+        " ".repeat(30),
+        dir
+    );
+    println!(
+        "{}                     {:4} / {:9} / {:3} / {:3} / {:4} / {:4}",
+        " ".repeat(30),
+        stats.nack_pkts,
+        stats.nack_seqs,
+        stats.pli_pkts,
+        stats.fir_pkts,
+        stats.remb_pkts,
+        stats.twcc_pkts
+    );
+    if verbose {
+        for (ts, kind) in stats.keyframe_requests.iter() {
+            println!(
+                "{}     {} {:?}",
+                " ".repeat(30),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                kind
+            );
+        }
+        for (ts, seqs) in stats.retransmission_requests.iter() {
+            println!(
+                "{}     {} Nack {:?}",
+                " ".repeat(30),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                seqs
+            );
+        }
+    }
+}
+
+fn print_delay_variation_stats(dir: &str, stats: &Option<flow::transport::DelayVariationStats>, verbose: bool) {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    println!(
+        "{} send_delay_var_usec {:>8}: avg / max",
+        " ".repeat(30),
+        dir
+    );
+    println!(
+        "{}                          {:6} / {:6}",
+        " ".repeat(30),
+        stats.avg,
+        stats.max
+    );
+    if verbose {
+        for (ts, var) in stats.samples.iter() {
+            println!(
+                "{}     {} {}",
+                " ".repeat(30),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                var
+            );
+        }
+    }
+}
+
+fn print_twcc_comparison(dir: &str, stats: &Option<flow::transport::TwccComparison>, verbose: bool) {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    println!(
+        "{} twcc {}: feedback / received / lost / lost_after_capture / delta_diff_usec avg / max",
+        " ".repeat(30),
+        dir
+    );
+    println!(
+        "{}                 {:8} / {:8} / {:4} / {:18} / {:19} / {:6}",
+        " ".repeat(30),
+        stats.feedback_pkts,
+        stats.reported_received,
+        stats.reported_lost,
+        stats.lost_after_capture,
+        stats.delta_diff_avg,
+        stats.delta_diff_max
+    );
+    if verbose {
+        for (ts, reported, observed) in stats.samples.iter() {
+            println!(
+                "{}     {} {} / {}",
+                " ".repeat(30),
+                DateTime::<Local>::from(*ts).format("%H:%M:%S.%3f"),
+                reported,
+                observed
+            );
+        }
+    }
+}
+
+fn print_bitrate_windows(windows: &[flow::bitrate::BitrateWindow], verbose: bool) {
+    use flow::bitrate::EstimateUsage;
+    if windows.is_empty() {
+        return;
+    }
+    let kbps = |bps: Option<u64>| match bps {
+        Some(bps) => format!("{}", bps / 1000),
+        None => "-".to_string(),
+    };
+    let avg = windows.iter().map(|w| w.total()).sum::<u64>() / windows.len() as u64;
+    let max = windows.iter().map(|w| w.total()).max().unwrap();
+    println!(
+        "{}kbps avg / max {:>6} / {:<6} last remb / twcc {:>6} / {:<6}",
+        " ".repeat(10),
+        avg / 1000,
+        max / 1000,
+        kbps(windows.last().unwrap().remb),
+        kbps(windows.last().unwrap().twcc)
+    );
+    for (start, end, usage) in flow::bitrate::usage_periods(windows).iter() {
+        if *usage == EstimateUsage::Overshoot || *usage == EstimateUsage::Underuse {
+            println!(
+                "{}{:?} {} ~ {}",
+                " ".repeat(10),
+                usage,
+                DateTime::<Local>::from(*start).format("%H:%M:%S"),
+                DateTime::<Local>::from(*end).format("%H:%M:%S")
+            );
+        }
+    }
+    if verbose {
+        for w in windows.iter() {
+            println!(
+                "{}     {} payload / overhead / padding / remb / twcc kbps {:6} / {:6} / {:6} / {:6} / {:6}",
+                " ".repeat(30),
+                DateTime::<Local>::from(w.start).format("%H:%M:%S"),
+                w.payload / 1000,
+                w.overhead / 1000,
+                w.padding / 1000,
+                kbps(w.remb),
+                kbps(w.twcc)
+            );
+        }
+    }
+}
+
+fn print_audio_level_stats(dir: &str, stats: &Option<flow::audio_level::AudioLevelStats>) {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    println!(
+        "{} audio_level {:>8}: speaking / loudest_dbov / quietest_dbov / avg_dbov",
+        " ".repeat(30),
+        dir
+    );
+    println!(
+        "{}                      {:7.2}% / {:12} / {:13} / {:8.1}{}",
+        " ".repeat(30),
+        stats.level.speaking_rate(),
+        stats.level.loudest,
+        stats.level.quietest,
+        stats.level.avg,
+        if stats.level.silent() { " (always silent, one-way audio)" } else { "" }
+    );
+    for (csrc, level) in stats.contributors.iter() {
+        println!(
+            "{}     csrc 0x{:08X} {:7.2}% / {:12} / {:13} / {:8.1}",
+            " ".repeat(30),
+            csrc,
+            level.speaking_rate(),
+            level.loudest,
+            level.quietest,
+            level.avg
+        );
+    }
+}
+
+fn print_timestamp_stats(dir: &str, stats: &flow::timestamp::TimestampStats, verbose: bool) {
+    use flow::timestamp::AnomalyKind;
+    println!(
+        "{} timestamp {:>8}: jump / backward / seq_mismatch / talk_spurts",
+        " ".repeat(30),
+        dir
+    );
+    println!(
+        "{}                     {:4} / {:8} / {:12} / {:11}",
+        " ".repeat(30),
+        stats.count(AnomalyKind::Jump),
+        stats.count(AnomalyKind::Backward),
+        stats.count(AnomalyKind::SeqMismatch),
+        stats.talk_spurts
+    );
+    if verbose {
+        for a in stats.anomalies.iter() {
+            println!(
+                "{}     {} {:?} seq={} delta={}",
+                " ".repeat(30),
+                DateTime::<Local>::from(a.ts).format("%H:%M:%S.%3f"),
+                a.kind,
+                a.seqnum,
+                a.delta
+            );
+        }
+    }
+}
+
+fn print_window_stats(dir: &str, windows: &[flow::window::WindowStats]) {
+    println!(
+        "{} window    {:>8}: time / lost / jitter_msec / kbps / pps / delay_avg_usec / delay_max_usec",
+        " ".repeat(30),
+        dir
+    );
+    for w in windows.iter() {
+        println!(
+            "{}     {} / {:7.4}% / {:11.2} / {:4} / {:3.0} / {:14} / {:14}",
+            " ".repeat(30),
+            DateTime::<Local>::from(w.start).format("%H:%M:%S"),
+            w.lost_rate,
+            w.jitter as f64 / 1000.0,
+            w.bitrate / 1000,
+            w.pkt_rate,
+            w.delay_avg,
+            w.delay_max
+        );
+    }
+}
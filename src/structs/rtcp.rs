@@ -1,23 +1,254 @@
 use serde::Serialize;
 use super::NoiseLevel;
 
+pub const RTCP_SR: u8 = 200;
+pub const RTCP_RR: u8 = 201;
+pub const RTCP_SDES: u8 = 202;
+pub const RTCP_BYE: u8 = 203;
+pub const RTCP_APP: u8 = 204;
+pub const RTCP_RTPFB: u8 = 205;
+pub const RTCP_PSFB: u8 = 206;
+pub const RTCP_XR: u8 = 207;
+
 #[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct RtcpHeader {
     pub rc: u8, // reception report count
-    pub padding: u8, // padding 
+    pub padding: u8, // padding
     pub version: u8, // version = 2
     pub payload: u8, // packet type
     pub length: u16, // length minus one, include header and padding, typical value: htons(1)
     pub ssrc: u32,
 }
 #[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct ReportBlock {
+    pub ssrc: u32,
+    pub fraction_lost: u8,
+    pub cumulative_lost: i32, // 24 bit signed
+    pub highest_seq: u32, // extended highest sequence number received
+    pub jitter: u32, // interarrival jitter, timestamp units
+    pub lsr: u32, // middle 32 bits of last SR NTP timestamp
+    pub dlsr: u32, // delay since last SR, 1/65536 seconds
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct SenderInfo {
+    pub ntp_sec: u32,
+    pub ntp_frac: u32,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+}
+
+impl SenderInfo {
+    /// middle 32 bits of NTP timestamp, as echoed in LSR
+    pub fn ntp_middle(&self) -> u32 {
+        (self.ntp_sec << 16) | (self.ntp_frac >> 16)
+    }
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct SenderReport {
+    pub ssrc: u32,
+    pub sender_info: SenderInfo,
+    pub reports: Vec<ReportBlock>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct ReceiverReport {
+    pub ssrc: u32,
+    pub reports: Vec<ReportBlock>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum SdesItem {
+    Cname(String),
+    Name(String),
+    Email(String),
+    Phone(String),
+    Loc(String),
+    Tool(String),
+    Note(String),
+    Priv(Vec<u8>),
+    Unknown(u8, Vec<u8>),
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct SdesChunk {
+    pub ssrc: u32,
+    pub items: Vec<SdesItem>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct Goodbye {
+    pub sources: Vec<u32>,
+    pub reason: Option<String>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct App {
+    pub ssrc: u32,
+    pub subtype: u8,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+/// Loss RLE and Duplicate RLE report block
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct RleReport {
+    pub thinning: u8,
+    pub ssrc: u32,
+    pub begin_seq: u16,
+    pub end_seq: u16,
+    pub chunks: Vec<u16>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct StatisticsSummary {
+    pub flags: u8, // loss, duplicate, jitter, ttl/hop limit
+    pub ssrc: u32,
+    pub begin_seq: u16,
+    pub end_seq: u16,
+    pub lost_packets: u32,
+    pub dup_packets: u32,
+    pub min_jitter: u32,
+    pub max_jitter: u32,
+    pub mean_jitter: u32,
+    pub dev_jitter: u32,
+    pub min_ttl: u8,
+    pub max_ttl: u8,
+    pub mean_ttl: u8,
+    pub dev_ttl: u8,
+}
+/// DLRR sub-block, the answer to a receiver reference time block
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct DlrrItem {
+    pub ssrc: u32,
+    pub lrr: u32, // middle 32 bits of the ntp time, like lsr
+    pub dlrr: u32, // 1/65536 sec
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct VoipMetrics {
+    pub ssrc: u32,
+    pub loss_rate: u8, // fraction, 1/256
+    pub discard_rate: u8, // fraction, 1/256
+    pub burst_density: u8,
+    pub gap_density: u8,
+    pub burst_duration: u16, // msec
+    pub gap_duration: u16, // msec
+    pub round_trip_delay: u16, // msec
+    pub end_system_delay: u16, // msec
+    pub signal_level: i8, // dBm
+    pub noise_level: i8, // dBm
+    pub rerl: u8,
+    pub gmin: u8,
+    pub r_factor: u8, // 127: unavailable
+    pub ext_r_factor: u8,
+    pub mos_lq: u8, // 1/10, 127: unavailable
+    pub mos_cq: u8,
+    pub rx_config: u8,
+    pub jb_nominal: u16, // msec
+    pub jb_maximum: u16,
+    pub jb_abs_max: u16,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum XrBlock {
+    LossRle(RleReport),
+    DuplicateRle(RleReport),
+    ReceiverReferenceTime { ntp_sec: u32, ntp_frac: u32 },
+    Dlrr(Vec<DlrrItem>),
+    StatisticsSummary(StatisticsSummary),
+    VoipMetrics(VoipMetrics),
+    Unknown(u8, Vec<u8>),
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct ExtendedReport {
+    pub ssrc: u32,
+    pub blocks: Vec<XrBlock>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct Nack {
+    pub sender_ssrc: u32,
+    pub media_ssrc: u32,
+    pub lost: Vec<u16>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct TwccPacket {
+    pub seq: u16,
+    pub delta: Option<i32>, // receive delta, 250 usec; None if not received
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct TransportCc {
+    pub sender_ssrc: u32,
+    pub media_ssrc: u32,
+    pub base_seq: u16,
+    pub status_count: u16,
+    pub reference_time: i32, // 64 msec
+    pub fb_count: u8,
+    pub packets: Vec<TwccPacket>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum TransportFeedback {
+    Nack(Nack),
+    TransportCc(TransportCc),
+    Unknown { fmt: u8, sender_ssrc: u32, media_ssrc: u32, fci: Vec<u8> },
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct FirEntry {
+    pub ssrc: u32,
+    pub seq: u8,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct Remb {
+    pub sender_ssrc: u32,
+    pub bitrate: u64, // bps
+    pub ssrcs: Vec<u32>,
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum PayloadFeedback {
+    Pli { sender_ssrc: u32, media_ssrc: u32 },
+    Fir { sender_ssrc: u32, entries: Vec<FirEntry> },
+    Remb(Remb),
+    Unknown { fmt: u8, sender_ssrc: u32, media_ssrc: u32, fci: Vec<u8> },
+}
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum RtcpPacket {
+    SenderReport(SenderReport),
+    ReceiverReport(ReceiverReport),
+    SourceDescription(Vec<SdesChunk>),
+    Goodbye(Goodbye),
+    App(App),
+    ExtendedReport(ExtendedReport),
+    TransportFeedback(TransportFeedback),
+    PayloadFeedback(PayloadFeedback),
+    Unknown(RtcpHeader, Vec<u8>),
+}
+/// A compound packet, `header` is the common header of the first sub-packet
+#[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct RTCP {
     pub header: RtcpHeader,
-    pub payload: Vec<u8>,
+    pub packets: Vec<RtcpPacket>,
+    /// size of the compound packet
+    pub len: usize,
 }
 
 impl RTCP {
     pub fn noise_level(&self) -> NoiseLevel {
         NoiseLevel::Zero
     }
+    pub fn sender_reports(&self) -> impl Iterator<Item = &SenderReport> {
+        self.packets.iter().filter_map(|p| match p {
+            RtcpPacket::SenderReport(sr) => Some(sr),
+            _ => None,
+        })
+    }
+    pub fn xr_blocks(&self) -> impl Iterator<Item = &XrBlock> {
+        self.packets.iter().flat_map(|p| match p {
+            RtcpPacket::ExtendedReport(xr) => &xr.blocks[..],
+            _ => &[][..],
+        })
+    }
+    /// report blocks of all SR and RR sub-packets, with the reporter ssrc
+    pub fn report_blocks(&self) -> impl Iterator<Item = (u32, &ReportBlock)> {
+        self.packets
+            .iter()
+            .flat_map(|p| {
+                let (ssrc, reports) = match p {
+                    RtcpPacket::SenderReport(sr) => (sr.ssrc, &sr.reports[..]),
+                    RtcpPacket::ReceiverReport(rr) => (rr.ssrc, &rr.reports[..]),
+                    _ => (0, &[][..]),
+                };
+                reports.iter().map(move |r| (ssrc, r))
+            })
+    }
 }
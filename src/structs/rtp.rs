@@ -12,15 +12,39 @@ pub struct RtpHeader {
     pub seqnum: u16,
     pub timestamp: u32,
     pub ssrc: u32,
+    /// contributing sources listed by a mixer
+    pub csrcs: Vec<u32>,
+    pub extensions: Vec<HeaderExtension>,
+}
+
+impl RtpHeader {
+    pub fn extension(&self, id: u8) -> Option<&[u8]> {
+        self.extensions.iter().find(|e| e.id == id).map(|e| &e.data[..])
+    }
+}
+
+/// RFC 8285 header extension element
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub struct HeaderExtension {
+    pub id: u8,
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct RTP {
     pub header: RtpHeader,
     pub payload: Vec<u8>,
+    /// size on the wire, header included
+    pub len: usize,
+    /// padding octets removed from the payload
+    pub padding_len: usize,
 }
 
 impl RTP {
+    /// padding without media, sent to probe or fill the bandwidth
+    pub fn padding_only(&self) -> bool {
+        self.padding_len > 0 && self.payload.is_empty()
+    }
     pub fn noise_level(&self) -> NoiseLevel {
         NoiseLevel::Zero
     }